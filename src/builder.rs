@@ -0,0 +1,149 @@
+use crossbeam::channel::{Receiver, Sender};
+use std::collections::HashMap;
+
+use crate::controller::{RustDroneCommand, RustDroneEvent};
+use crate::delay::ProcessingDelay;
+use crate::drone::RustDrone;
+use crate::fault::FaultInjection;
+use crate::hook::PacketHook;
+use crate::link::LinkQuality;
+use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::drone::Drone;
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+/// Builds a `RustDrone` out of the channels required by the protocol and the optional
+/// features layered on top of them, all of them off unless set
+pub struct RustDroneBuilder {
+    id: NodeId,
+    controller_send: Sender<DroneEvent>,
+    controller_recv: Receiver<DroneCommand>,
+    packet_recv: Receiver<Packet>,
+    packet_send: HashMap<NodeId, Sender<Packet>>,
+    pdr: f32,
+    options: Vec<Box<dyn FnOnce(RustDrone) -> RustDrone + Send>>,
+}
+
+impl RustDroneBuilder {
+    /// Start from a drone with no neighbours that never drops fragments
+    pub fn new(
+        id: NodeId,
+        controller_send: Sender<DroneEvent>,
+        controller_recv: Receiver<DroneCommand>,
+        packet_recv: Receiver<Packet>,
+    ) -> Self {
+        Self {
+            id,
+            controller_send,
+            controller_recv,
+            packet_recv,
+            packet_send: HashMap::new(),
+            pdr: 0.0,
+            options: Vec::new(),
+        }
+    }
+
+    /// Connect the drone to `neighbour`
+    pub fn neighbour(mut self, neighbour: NodeId, packet_send: Sender<Packet>) -> Self {
+        self.packet_send.insert(neighbour, packet_send);
+        self
+    }
+
+    /// Drop fragments with probability `pdr`
+    pub fn pdr(mut self, pdr: f32) -> Self {
+        self.pdr = pdr;
+        self
+    }
+
+    /// Seed the random decisions of the drone, see `RustDrone::with_seed`
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.options
+            .push(Box::new(move |drone| drone.with_seed(seed)));
+        self
+    }
+
+    /// See `RustDrone::with_processing_delay`
+    pub fn processing_delay(mut self, processing_delay: ProcessingDelay) -> Self {
+        self.options.push(Box::new(move |drone| {
+            drone.with_processing_delay(processing_delay)
+        }));
+        self
+    }
+
+    /// See `RustDrone::with_link_quality`
+    pub fn link_quality(mut self, neighbour: NodeId, link_quality: LinkQuality) -> Self {
+        self.options.push(Box::new(move |drone| {
+            drone.with_link_quality(neighbour, link_quality)
+        }));
+        self
+    }
+
+    /// See `RustDrone::with_fault_injection`
+    pub fn fault_injection(mut self, fault_injection: FaultInjection) -> Self {
+        self.options.push(Box::new(move |drone| {
+            drone.with_fault_injection(fault_injection)
+        }));
+        self
+    }
+
+    /// Bound the packets the drone keeps while paused, see `RustDrone::with_pause_buffer_capacity`
+    pub fn pause_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.options.push(Box::new(move |drone| {
+            drone.with_pause_buffer_capacity(capacity)
+        }));
+        self
+    }
+
+    /// See `RustDrone::with_log_target`
+    pub fn log_target(mut self, log_target: impl Into<String>) -> Self {
+        let log_target = log_target.into();
+        self.options
+            .push(Box::new(move |drone| drone.with_log_target(log_target)));
+        self
+    }
+
+    /// See `RustDrone::with_event_send`
+    pub fn event_send(mut self, event_send: Sender<RustDroneEvent>) -> Self {
+        self.options
+            .push(Box::new(move |drone| drone.with_event_send(event_send)));
+        self
+    }
+
+    /// See `RustDrone::with_command_recv`
+    pub fn command_recv(mut self, command_recv: Receiver<RustDroneCommand>) -> Self {
+        self.options
+            .push(Box::new(move |drone| drone.with_command_recv(command_recv)));
+        self
+    }
+
+    /// See `RustDrone::with_hook`
+    pub fn hook(mut self, hook: Box<dyn PacketHook>) -> Self {
+        self.options
+            .push(Box::new(move |drone| drone.with_hook(hook)));
+        self
+    }
+
+    /// Any other option of `RustDrone`, e.g. `builder.customize(|d| d.with_max_hops(8))`
+    pub fn customize(
+        mut self,
+        customize: impl FnOnce(RustDrone) -> RustDrone + Send + 'static,
+    ) -> Self {
+        self.options.push(Box::new(customize));
+        self
+    }
+
+    /// Create the drone and apply the options in the order they were given
+    pub fn build(self) -> RustDrone {
+        let drone = RustDrone::new(
+            self.id,
+            self.controller_send,
+            self.controller_recv,
+            self.packet_recv,
+            self.packet_send,
+            self.pdr,
+        );
+        self.options
+            .into_iter()
+            .fold(drone, |drone, option| option(drone))
+    }
+}
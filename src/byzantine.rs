@@ -0,0 +1,43 @@
+use std::collections::HashSet;
+
+use wg_2024::network::NodeId;
+
+/// Probabilities with which a byzantine `RustDrone` misbehaves on every packet it forwards.
+/// Such a drone is meant to test how robust clients are against faulty relays
+#[derive(Debug, Clone, Default)]
+pub struct ByzantineBehaviour {
+    /// Flip a byte within the length of a non empty fragment
+    pub corrupt_payload: f32,
+    /// Forward the packet twice
+    pub duplicate: f32,
+    /// Add a node that does not exist to the path trace of a flood response
+    pub forge_path_trace: f32,
+    /// Swap two entries in the path trace of a flood response
+    pub reorder_path_trace: f32,
+    /// Point the routing header to a wrong hop
+    pub corrupt_hop_index: f32,
+    /// Forward a fragment but tell its sender it was dropped
+    pub bogus_nack: f32,
+}
+
+/// A selective forwarding attacker, it silently drops fragments headed to some destinations
+/// while forwarding everything else normally
+#[derive(Debug, Clone, Default)]
+pub struct SelectiveForwarding {
+    /// Destinations whose fragments are dropped
+    pub targets: HashSet<NodeId>,
+    /// Share of their fragments that is dropped, 1 for a blackhole
+    pub drop_rate: f32,
+}
+
+/// What a byzantine drone did to a packet, reported with `RustDroneEvent::Byzantine`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ByzantineAction {
+    CorruptedPayload,
+    Duplicated,
+    ForgedPathTrace,
+    ReorderedPathTrace,
+    CorruptedHopIndex,
+    BogusNack,
+    SilentlyDropped,
+}
@@ -0,0 +1,119 @@
+use crossbeam::channel::{at, bounded, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+
+/// Source of time for the features of `RustDrone` that depend on it
+pub trait Clock: Send {
+    /// Returns the current time
+    fn now(&self) -> Instant;
+
+    /// Returns a receiver that delivers a message once `deadline` is reached
+    fn at(&self, deadline: Instant) -> Receiver<Instant>;
+}
+
+/// Clock following the real time
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn at(&self, deadline: Instant) -> Receiver<Instant> {
+        at(deadline)
+    }
+}
+
+/// Clock that only moves when told to, so tests can skip over delays without sleeping.
+/// Clones share the same time, keep one to drive the clock given to the drone.
+/// Each clone has at most one timer, like a drone only ever waits for one deadline
+#[derive(Default)]
+pub struct VirtualClock {
+    state: Arc<Mutex<VirtualClockState>>,
+    handle: usize,
+}
+
+struct VirtualClockState {
+    now: Instant,
+    /// Pending timers, with the handle of the clone that set them
+    timers: Vec<(usize, Instant, Sender<Instant>)>,
+    last_handle: usize,
+}
+
+impl Default for VirtualClockState {
+    fn default() -> Self {
+        Self {
+            now: Instant::now(),
+            timers: Vec::new(),
+            last_handle: 0,
+        }
+    }
+}
+
+impl Clone for VirtualClock {
+    fn clone(&self) -> Self {
+        let mut state = self.state();
+        state.last_handle += 1;
+        Self {
+            state: self.state.clone(),
+            handle: state.last_handle,
+        }
+    }
+}
+
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the time forward, waking up everyone waiting for a deadline that was reached
+    pub fn advance(&self, duration: Duration) {
+        let mut state = self.state();
+        state.now += duration;
+
+        let now = state.now;
+        state.timers.retain(|(_, deadline, timer)| {
+            if *deadline > now {
+                return true;
+            }
+            // the receiver may be gone already, nobody is waiting then
+            let _ = timer.try_send(now);
+            false
+        });
+    }
+
+    /// Returns the earliest deadline someone is waiting for, if any
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.state()
+            .timers
+            .iter()
+            .map(|(_, deadline, _)| *deadline)
+            .min()
+    }
+
+    fn state(&self) -> MutexGuard<'_, VirtualClockState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.state().now
+    }
+
+    /// Replaces the timer previously set by this clone, which never fires then
+    fn at(&self, deadline: Instant) -> Receiver<Instant> {
+        let mut state = self.state();
+        let (timer, timeout) = bounded(1);
+
+        // the drone dropped the previous timer when it computed a new wake up,
+        // keeping it around would pile up a timer for every packet
+        state.timers.retain(|(handle, _, _)| *handle != self.handle);
+        if deadline <= state.now {
+            let _ = timer.try_send(state.now);
+        } else {
+            state.timers.push((self.handle, deadline, timer));
+        }
+        timeout
+    }
+}
@@ -0,0 +1,216 @@
+use crate::byzantine::ByzantineAction;
+use crate::fault::Fault;
+use crate::link::LinkQuality;
+use crate::stats::DroneStats;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use wg_2024::controller::DroneEvent;
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+/// Events reported by `RustDrone` on top of the `DroneEvent`s required by the protocol
+#[derive(Debug, Clone, PartialEq)]
+pub enum RustDroneEvent {
+    /// The drone entered its main loop
+    Started(NodeId),
+    /// The drone received a crash command and is crashing according to its `CrashMode`
+    Crashing(NodeId),
+    /// The drone finished crashing and is about to exit
+    Crashed(NodeId),
+    /// The drone exited because its packet channel was closed
+    Stopped(NodeId),
+    /// Reply to `RustDroneCommand::QueryNeighbours` with the ids the drone can send to
+    Neighbours(NodeId, Vec<NodeId>),
+    /// The drone refused to forward a packet whose remaining route loops, a NACK was returned
+    CyclicRoute(NodeId, Packet),
+    /// The drone refused to forward a packet whose route is longer than allowed, a NACK was returned
+    RouteTooLong(NodeId, Packet),
+    /// The drone received a packet whose routing header has no current hop
+    MalformedPacket(NodeId, Packet),
+    /// The drone dropped a packet because the queue of the neighbour (second id) was full
+    QueueOverflow(NodeId, NodeId, Packet),
+    /// The drone dropped a fragment it had already forwarded
+    DuplicateSuppressed(NodeId, Packet),
+    /// The drone dropped a packet because it was over its rate limit and its queue was full
+    RateLimited(NodeId, Packet),
+    /// The drone intentionally misbehaved on a packet, which is reported as it was forwarded
+    Byzantine(NodeId, ByzantineAction, Packet),
+    /// The drone injected a fault while forwarding a packet
+    FaultInjected(NodeId, Fault, Packet),
+    /// The drone ran out of energy and is about to crash
+    EnergyDepleted(NodeId),
+    /// The drone stopped forwarding packets after `RustDroneCommand::Pause`
+    Paused(NodeId),
+    /// The drone went back to forwarding packets after `RustDroneCommand::Resume`
+    Resumed(NodeId),
+    /// Reply to `RustDroneCommand::ReportStats` with the counters of the drone
+    Stats(NodeId, DroneStats),
+    /// Sent periodically by a drone set up with `RustDrone::with_heartbeat`
+    Heartbeat(NodeId, Heartbeat),
+    /// Reply to `RustDroneCommand::QuerySnapshot` with the current state of the drone
+    Snapshot(NodeId, DroneSnapshot),
+    /// Reply to `RustDroneCommand::Sync` with its id
+    Synced(NodeId, u64),
+}
+
+impl RustDroneEvent {
+    /// Returns the id of the drone that reported the event
+    pub fn drone_id(&self) -> NodeId {
+        match self {
+            RustDroneEvent::Started(id)
+            | RustDroneEvent::Crashing(id)
+            | RustDroneEvent::Crashed(id)
+            | RustDroneEvent::Stopped(id)
+            | RustDroneEvent::Neighbours(id, _)
+            | RustDroneEvent::CyclicRoute(id, _)
+            | RustDroneEvent::RouteTooLong(id, _)
+            | RustDroneEvent::MalformedPacket(id, _)
+            | RustDroneEvent::QueueOverflow(id, _, _)
+            | RustDroneEvent::DuplicateSuppressed(id, _)
+            | RustDroneEvent::RateLimited(id, _)
+            | RustDroneEvent::Byzantine(id, _, _)
+            | RustDroneEvent::FaultInjected(id, _, _)
+            | RustDroneEvent::EnergyDepleted(id)
+            | RustDroneEvent::Paused(id)
+            | RustDroneEvent::Resumed(id)
+            | RustDroneEvent::Stats(id, _)
+            | RustDroneEvent::Heartbeat(id, _)
+            | RustDroneEvent::Snapshot(id, _)
+            | RustDroneEvent::Synced(id, _) => *id,
+        }
+    }
+}
+
+/// Sign of life of a drone, reported with `RustDroneEvent::Heartbeat`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Heartbeat {
+    /// Time since the drone entered its main loop
+    pub uptime: Duration,
+    /// Packets waiting to be handled or delivered by the drone
+    pub queue_depth: usize,
+}
+
+/// State of a running drone, reported with `RustDroneEvent::Snapshot`
+#[derive(Debug, Clone, PartialEq)]
+pub struct DroneSnapshot {
+    /// Ids the drone can send to, sorted
+    pub neighbours: Vec<NodeId>,
+    /// Last packet drop rate the drone was given
+    pub pdr: f32,
+    pub status: DroneStatus,
+    /// Packets waiting to be handled or delivered by the drone
+    pub queue_length: usize,
+}
+
+/// Whether a drone is forwarding packets, see `DroneSnapshot`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DroneStatus {
+    Running,
+    /// Buffering packets after `RustDroneCommand::Pause`
+    Paused,
+}
+
+/// Keeps track of the events of the drones to flag the ones that went silent,
+/// which tells a deadlocked drone apart from an idle one
+#[derive(Debug, Clone)]
+pub struct HeartbeatWatchdog {
+    timeout: Duration,
+    last_seen: HashMap<NodeId, Instant>,
+}
+
+impl HeartbeatWatchdog {
+    /// Flag the drones that sent nothing for longer than `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// Take into account an event received at `now`. Any event is a sign of life, so idle drones
+    /// without heartbeats can be pinged with `RustDroneCommand::Sync`.
+    /// Drones that crashed or stopped are forgotten
+    pub fn record(&mut self, event: &RustDroneEvent, now: Instant) {
+        match event {
+            RustDroneEvent::Crashed(id) | RustDroneEvent::Stopped(id) => {
+                self.last_seen.remove(id);
+            }
+            event => {
+                self.last_seen.insert(event.drone_id(), now);
+            }
+        }
+    }
+
+    /// Returns the drones whose last sign of life is older than the timeout, sorted by id
+    pub fn silent_drones(&self, now: Instant) -> Vec<NodeId> {
+        let mut silent = self
+            .last_seen
+            .iter()
+            .filter(|(_, last_seen)| now.saturating_duration_since(**last_seen) > self.timeout)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>();
+        silent.sort_unstable();
+        silent
+    }
+}
+
+/// A `DroneEvent` together with the time it was sent and the drone that sent it
+#[derive(Debug, Clone)]
+pub struct EventEnvelope {
+    pub timestamp: Instant,
+    pub origin: NodeId,
+    pub event: DroneEvent,
+}
+
+/// Commands understood by `RustDrone` on top of the `DroneCommand`s required by the protocol
+#[derive(Debug, Clone, PartialEq)]
+pub enum RustDroneCommand {
+    /// Ask the drone to report its current neighbours with `RustDroneEvent::Neighbours`
+    QueryNeighbours,
+    /// Crash the drone in the given mode, instead of the one it was set up with
+    Crash(CrashMode),
+    /// Change the quality of the link towards a single neighbour
+    SetLinkQuality(NodeId, LinkQuality),
+    /// Change only the drop rate of the link towards a single neighbour
+    SetLinkPdr(NodeId, f32),
+    /// Stop forwarding packets, buffering the ones received until resumed
+    Pause,
+    /// Forward the buffered packets in order and go back to normal
+    Resume,
+    /// Ask the drone to report its counters with `RustDroneEvent::Stats`
+    ReportStats,
+    /// Zero the counters of the drone, to compare them before and after an intervention
+    ResetStats,
+    /// Ask the drone to report its current state with `RustDroneEvent::Snapshot`
+    QuerySnapshot,
+    /// Ask the drone to confirm with `RustDroneEvent::Synced` once it applied every command
+    /// sent before this one, on this channel or on the `DroneCommand` one
+    Sync(u64),
+}
+
+/// How a drone behaves once it crashes
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CrashMode {
+    /// Keep handling the packets in flight until the packet channel is closed
+    #[default]
+    Graceful,
+    /// Like `Graceful`, but give up after the timeout even if the packet channel is still open
+    DrainWithTimeout(Duration),
+    /// Drop every pending packet and exit right away
+    Immediate,
+    /// Stop processing anything but keep the channels open, like a hung node,
+    /// until the controller goes away
+    Unresponsive,
+}
+
+/// What a drone does with a packet whose routing header has no current hop
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MalformedPacketPolicy {
+    /// Discard it, only leaving a trace in the logs
+    Drop,
+    /// Report it with `RustDroneEvent::MalformedPacket`
+    #[default]
+    Report,
+    /// Report it and also hand it to the controller with a `ControllerShortcut`
+    Shortcut,
+}
@@ -0,0 +1,25 @@
+use rand::{Rng, RngCore};
+use std::time::Duration;
+
+/// Distribution of the time a drone spends processing each packet
+#[derive(Debug, Clone)]
+pub enum ProcessingDelay {
+    /// Every packet takes the same time
+    Constant(Duration),
+    /// Uniformly distributed between a minimum and a maximum
+    Uniform(Duration, Duration),
+    /// Exponentially distributed with the given mean
+    Exponential(Duration),
+}
+
+impl ProcessingDelay {
+    pub fn sample(&self, rng: &mut dyn RngCore) -> Duration {
+        match self {
+            ProcessingDelay::Constant(delay) => *delay,
+            ProcessingDelay::Uniform(min, max) => {
+                *min + max.saturating_sub(*min).mul_f64(rng.random::<f64>())
+            }
+            ProcessingDelay::Exponential(mean) => mean.mul_f64(-(1.0 - rng.random::<f64>()).ln()),
+        }
+    }
+}
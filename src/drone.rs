@@ -1,25 +1,117 @@
-use crossbeam::channel::{select, select_biased, Receiver, Sender};
+use crossbeam::channel::{never, select_biased, Receiver, Sender, TrySendError};
 use log::{debug, error, info, trace, warn};
-use rand::Rng;
-use std::collections::{HashMap, HashSet};
+use rand::rngs::StdRng;
+use rand::{Rng, RngCore, SeedableRng};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::byzantine::{ByzantineAction, ByzantineBehaviour, SelectiveForwarding};
+use crate::clock::{Clock, SystemClock};
+use crate::controller::{
+    CrashMode, DroneSnapshot, DroneStatus, EventEnvelope, Heartbeat, MalformedPacketPolicy,
+    RustDroneCommand, RustDroneEvent,
+};
+use crate::delay::ProcessingDelay;
+use crate::drop_model::{ControlPdr, DropModel, Uniform};
+use crate::energy::EnergyBudget;
+use crate::fault::{Fault, FaultInjection};
+use crate::hook::{HookVerdict, PacketHook};
+use crate::link::LinkQuality;
+use crate::rate_limit::{RateLimit, RateLimiter};
+use crate::stats::{DroneStats, SessionStats, MAX_TRACKED_SESSIONS};
 use wg_2024::controller::{DroneCommand, DroneEvent};
 use wg_2024::drone::Drone;
 use wg_2024::network::{NodeId, SourceRoutingHeader};
 use wg_2024::packet::{FloodRequest, FloodResponse, Nack, NackType, NodeType, Packet, PacketType};
 
+/// Most packets taken from the channel at once, so commands are not kept waiting by a burst
+const MAX_PACKET_BURST: usize = 64;
+
+/// Packets a paused drone keeps by default, the ones above are dropped
+const DEFAULT_PAUSE_BUFFER_CAPACITY: usize = 1024;
+
+/// Longest time a packet held back to be reordered waits for the next one
+const MAX_REORDER_HOLD: Duration = Duration::from_millis(50);
+
 /// Example of drone implementation
 pub struct RustDrone {
     id: NodeId,
     controller_send: Sender<DroneEvent>,
     controller_recv: Receiver<DroneCommand>,
     packet_recv: Receiver<Packet>,
+    drop_model: Box<dyn DropModel>,
     pdr: f32,
+    control_pdr: Option<ControlPdr>,
     packet_send: HashMap<NodeId, Sender<Packet>>,
     seen_flood_requests: HashSet<(NodeId, u64)>,
     log_target: String,
     state: DroneState,
+    event_send: Option<Sender<RustDroneEvent>>,
+    command_recv: Receiver<RustDroneCommand>,
+    clock: Box<dyn Clock>,
+    processing_delay: Option<ProcessingDelay>,
+    delayed_packets: VecDeque<(Instant, Packet)>,
+    malformed_packet_policy: MalformedPacketPolicy,
+    crash_mode: CrashMode,
+    byzantine: Option<ByzantineBehaviour>,
+    selective_forwarding: Option<SelectiveForwarding>,
+    link_quality: HashMap<NodeId, LinkQuality>,
+    envelope_send: Option<Sender<EventEnvelope>>,
+    rng: Box<dyn RngCore + Send>,
+    stats: DroneStats,
+    paused_packets: Option<VecDeque<Packet>>,
+    pause_buffer_capacity: usize,
+    energy: Option<EnergyBudget>,
+    link_busy_until: HashMap<NodeId, Instant>,
+    packets_in_transit: VecDeque<PacketInTransit>,
+    fault_injection: Option<FaultInjection>,
+    held_packet: Option<PacketInTransit>,
+    started_at: Instant,
+    heartbeat_interval: Option<Duration>,
+    next_heartbeat: Option<Instant>,
+    max_hops: Option<usize>,
+    hooks: Vec<Box<dyn PacketHook>>,
+    rate_limiter: Option<RateLimiter>,
+    tracked_sessions: VecDeque<u64>,
+    recent_fragments: Option<RecentFragments>,
+}
+
+/// A packet forwarded on a slow link, delivered once it reaches the other end
+struct PacketInTransit {
+    arrive_at: Instant,
+    next_hop: NodeId,
+    channel: Sender<Packet>,
+    packet: Packet,
+}
+
+/// The last fragments forwarded, as `(session_id, fragment_index)`, to spot duplicates
+struct RecentFragments {
+    capacity: usize,
+    seen: HashSet<(u64, u64)>,
+    order: VecDeque<(u64, u64)>,
+}
+
+impl RecentFragments {
+    /// Remembers the fragment, forgetting the oldest one if there is no room left
+    fn insert(&mut self, fragment: (u64, u64)) {
+        if !self.seen.insert(fragment) {
+            return;
+        }
+        self.order.push_back(fragment);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+    }
+
+    /// Forgets the fragment, so that it can be sent again
+    fn remove(&mut self, fragment: &(u64, u64)) {
+        if self.seen.remove(fragment) {
+            self.order.retain(|f| f != fragment);
+        }
+    }
 }
 
 enum CommandResult {
@@ -48,19 +140,106 @@ impl Drone for RustDrone {
             controller_send,
             controller_recv,
             packet_recv,
+            drop_model: Box::new(Uniform::new(pdr)),
             pdr,
+            control_pdr: None,
             packet_send,
             seen_flood_requests: HashSet::new(),
             log_target: format!("drone-{}", id),
             state: DroneState::Created,
+            event_send: None,
+            command_recv: never(),
+            clock: Box::new(SystemClock),
+            processing_delay: None,
+            delayed_packets: VecDeque::new(),
+            malformed_packet_policy: MalformedPacketPolicy::default(),
+            crash_mode: CrashMode::default(),
+            byzantine: None,
+            selective_forwarding: None,
+            link_quality: HashMap::new(),
+            envelope_send: None,
+            rng: Box::new(StdRng::from_os_rng()),
+            stats: DroneStats::default(),
+            paused_packets: None,
+            pause_buffer_capacity: DEFAULT_PAUSE_BUFFER_CAPACITY,
+            energy: None,
+            link_busy_until: HashMap::new(),
+            packets_in_transit: VecDeque::new(),
+            fault_injection: None,
+            held_packet: None,
+            started_at: Instant::now(),
+            heartbeat_interval: None,
+            next_heartbeat: None,
+            max_hops: None,
+            hooks: Vec::new(),
+            rate_limiter: None,
+            tracked_sessions: VecDeque::new(),
+            recent_fragments: None,
         }
     }
 
     fn run(&mut self) {
         trace!(target: &self.log_target, "Drone '{}' has started", self.id);
         self.state = DroneState::Running;
+        self.started_at = self.clock.now();
+        self.next_heartbeat = self
+            .heartbeat_interval
+            .map(|interval| self.started_at + interval);
+        self.send_event(RustDroneEvent::Started(self.id));
+
+        if let Some(control_pdr) = &self.control_pdr {
+            warn!(target: &self.log_target,
+                "Drone '{}' is NOT protocol conformant, it drops control packets with {:?}",
+                self.id, control_pdr
+            );
+        }
+
+        if let Some(byzantine) = &self.byzantine {
+            warn!(target: &self.log_target,
+                "Drone '{}' is byzantine, it misbehaves with {:?}",
+                self.id, byzantine
+            );
+        }
+
+        if let Some(selective_forwarding) = &self.selective_forwarding {
+            warn!(target: &self.log_target,
+                "Drone '{}' is byzantine, it selectively drops fragments with {:?}",
+                self.id, selective_forwarding
+            );
+        }
 
         loop {
+            self.handle_rate_limited_packets();
+            self.handle_delayed_packets();
+            self.deliver_packets_in_transit();
+            self.send_heartbeat();
+
+            if self.is_out_of_energy() {
+                warn!(target: &self.log_target, "Drone '{}' ran out of energy, crashing", self.id);
+                self.send_event(RustDroneEvent::EnergyDepleted(self.id));
+                self.handle_command(DroneCommand::Crash);
+                break;
+            }
+
+            // wake up when the next delayed packet is ready to be processed or delivered,
+            // when a rate limited packet gets its token or when the next heartbeat is due
+            let next_wake_up = [
+                self.delayed_packets.front().map(|(ready_at, _)| *ready_at),
+                self.packets_in_transit.front().map(|p| p.arrive_at),
+                self.held_packet.as_ref().map(|p| p.arrive_at),
+                self.next_heartbeat,
+                self.rate_limiter
+                    .as_ref()
+                    .and_then(RateLimiter::next_token_at),
+            ]
+            .into_iter()
+            .flatten()
+            .min();
+            let delay_timer = match next_wake_up {
+                Some(wake_up_at) => self.clock.at(wake_up_at),
+                None => never(),
+            };
+
             select_biased! {
                 recv(self.controller_recv) -> command => {
                     if let Ok(command) = command {
@@ -70,40 +249,507 @@ impl Drone for RustDrone {
                         }
                     }
                 },
+                recv(self.command_recv) -> command => {
+                    if let Ok(command) = command {
+                        match self.handle_rust_command(command) {
+                            CommandResult::Quit => break,
+                            CommandResult::Ok => {}
+                        }
+                    } else {
+                        debug!(target: &self.log_target, "Drone '{}' command channel closed", self.id);
+                        self.command_recv = never();
+                    }
+                },
                 recv(self.packet_recv) -> packet => {
                     if let Ok(packet) = packet {
-                        self.handle_packet(packet);
+                        self.receive_burst(packet);
                     }
                     else {
                         error!(target: &self.log_target, "Drone '{}' failed to receive packet, crashing", self.id);
                         break; // channel closed, exit the loop
                     }
                 },
+                recv(delay_timer) -> _ => {},
             }
         }
 
         if matches!(self.state, DroneState::Crashing) {
-            trace!(target: &self.log_target, "Drone '{}' is crashing state, waiting for Reciver to be closed", self.id);
-            loop {
-                select! {
-                    recv(self.packet_recv) -> packet => {
-                        if let Ok(packet) = packet {
-                            self.handle_packet(packet);
-                        }
-                        else {
-                            debug!(target: &self.log_target, "Drone '{}' Reciver closed, stopping", self.id);
-                            break;
-                        }
-                    }
+            match self.crash_mode {
+                CrashMode::Graceful => self.drain_packets(None),
+                CrashMode::DrainWithTimeout(timeout) => self.drain_packets(Some(timeout)),
+                CrashMode::Immediate => {
+                    debug!(target: &self.log_target,
+                        "Drone '{}' crashing immediately, dropping {} pending packets",
+                        self.id,
+                        self.delayed_packets.len()
+                            + self.rate_limiter.as_ref().map_or(0, |r| r.queue.len())
+                            + self.packets_in_transit.len()
+                            + usize::from(self.held_packet.is_some())
+                            + self.packet_recv.len()
+                    );
+                }
+                CrashMode::Unresponsive => {
+                    warn!(target: &self.log_target,
+                        "Drone '{}' is unresponsive, ignoring everything until the controller goes away",
+                        self.id
+                    );
+                    while self.controller_recv.recv().is_ok() {}
                 }
             }
+            self.send_event(RustDroneEvent::Crashed(self.id));
+        } else {
+            self.flush_pending_packets();
+            self.send_event(RustDroneEvent::Stopped(self.id));
         }
         trace!(target: &self.log_target, "Drone '{}' has succesfully stopped", self.id);
     }
 }
 
 impl RustDrone {
-    fn handle_packet(&mut self, packet: Packet) {
+    /// Log to `log_target` instead of `drone-{id}`
+    pub fn with_log_target(mut self, log_target: impl Into<String>) -> Self {
+        self.log_target = log_target.into();
+        self
+    }
+
+    /// Attach a channel on which the drone reports its `RustDroneEvent`s
+    pub fn with_event_send(mut self, event_send: Sender<RustDroneEvent>) -> Self {
+        self.event_send = Some(event_send);
+        self
+    }
+
+    /// Attach a channel on which the drone mirrors every `DroneEvent` it sends to the
+    /// controller, stamped with the time it was sent and the id of the drone
+    pub fn with_event_envelope_send(mut self, envelope_send: Sender<EventEnvelope>) -> Self {
+        self.envelope_send = Some(envelope_send);
+        self
+    }
+
+    /// Replace the uniform drop decision with a custom `DropModel`
+    pub fn with_drop_model(mut self, drop_model: Box<dyn DropModel>) -> Self {
+        self.drop_model = drop_model;
+        self
+    }
+
+    /// Also drop Acks, Nacks and flood packets with the given rates.
+    /// This breaks the protocol, which requires them to never be dropped
+    pub fn with_non_conformant_control_pdr(mut self, control_pdr: ControlPdr) -> Self {
+        self.control_pdr = Some(control_pdr);
+        self
+    }
+
+    /// Make the drone spend some time processing each packet before handling it
+    pub fn with_processing_delay(mut self, processing_delay: ProcessingDelay) -> Self {
+        self.processing_delay = Some(processing_delay);
+        self
+    }
+
+    /// Set what the drone does with packets with no current hop, they are reported by default
+    pub fn with_malformed_packet_policy(mut self, policy: MalformedPacketPolicy) -> Self {
+        self.malformed_packet_policy = policy;
+        self
+    }
+
+    /// Set how the drone behaves when it receives `DroneCommand::Crash`
+    pub fn with_crash_mode(mut self, crash_mode: CrashMode) -> Self {
+        self.crash_mode = crash_mode;
+        self
+    }
+
+    /// Make the drone intentionally tamper with the packets it forwards
+    pub fn with_byzantine_behaviour(mut self, byzantine: ByzantineBehaviour) -> Self {
+        self.byzantine = Some(byzantine);
+        self
+    }
+
+    /// Set the quality of the link towards `neighbour`
+    pub fn with_link_quality(mut self, neighbour: NodeId, link_quality: LinkQuality) -> Self {
+        self.link_quality.insert(neighbour, link_quality);
+        self
+    }
+
+    /// Drop the fragments sent to `neighbour` with `pdr` instead of the drone-wide rate
+    pub fn with_link_pdr(mut self, neighbour: NodeId, pdr: f32) -> Self {
+        self.link_quality.entry(neighbour).or_default().pdr = Some(pdr);
+        self
+    }
+
+    /// Take every random decision with `rng` instead of a generator seeded by the OS
+    pub fn with_rng(mut self, rng: Box<dyn RngCore + Send>) -> Self {
+        self.rng = rng;
+        self
+    }
+
+    /// Seed the random decisions of the drone, so a simulation can be reproduced
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.with_rng(Box::new(StdRng::seed_from_u64(seed)))
+    }
+
+    /// Make the drone silently drop fragments headed to some destinations
+    pub fn with_selective_forwarding(mut self, selective_forwarding: SelectiveForwarding) -> Self {
+        self.selective_forwarding = Some(selective_forwarding);
+        self
+    }
+
+    /// Set how many packets the drone buffers while paused with `RustDroneCommand::Pause`
+    pub fn with_pause_buffer_capacity(mut self, capacity: usize) -> Self {
+        self.pause_buffer_capacity = capacity;
+        self
+    }
+
+    /// Power the drone with a battery, it crashes once the energy runs out
+    pub fn with_energy_budget(mut self, energy: EnergyBudget) -> Self {
+        self.energy = Some(energy);
+        self
+    }
+
+    /// Replace the real time with another `Clock`, e.g. a `VirtualClock` driven by a test
+    pub fn with_clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Make the drone duplicate and reorder the packets it forwards
+    pub fn with_fault_injection(mut self, fault_injection: FaultInjection) -> Self {
+        self.fault_injection = Some(fault_injection);
+        self
+    }
+
+    /// Make the drone report a `RustDroneEvent::Heartbeat` every `interval`
+    pub fn with_heartbeat(mut self, interval: Duration) -> Self {
+        self.heartbeat_interval = Some(interval);
+        self
+    }
+
+    /// Refuse to forward packets whose route is longer than `max_hops` nodes
+    pub fn with_max_hops(mut self, max_hops: usize) -> Self {
+        self.max_hops = Some(max_hops);
+        self
+    }
+
+    /// Register a `PacketHook`, hooks run in the order they were registered
+    /// and the first one vetoing a packet stops the others
+    pub fn with_hook(mut self, hook: Box<dyn PacketHook>) -> Self {
+        self.hooks.push(hook);
+        self
+    }
+
+    /// Cap the packets the drone handles per second
+    pub fn with_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limiter = Some(RateLimiter::new(rate_limit));
+        self
+    }
+
+    /// Drop fragments identical to one of the last `capacity` the drone forwarded,
+    /// unless a NACK for them went through the drone since
+    pub fn with_duplicate_suppression(mut self, capacity: usize) -> Self {
+        self.recent_fragments = Some(RecentFragments {
+            capacity,
+            seen: HashSet::new(),
+            order: VecDeque::new(),
+        });
+        self
+    }
+
+    /// Attach a channel from which the drone receives `RustDroneCommand`s
+    pub fn with_command_recv(mut self, command_recv: Receiver<RustDroneCommand>) -> Self {
+        self.command_recv = command_recv;
+        self
+    }
+
+    fn send_controller_event(&self, event: DroneEvent) {
+        if let Some(envelope_send) = &self.envelope_send {
+            let envelope = EventEnvelope {
+                timestamp: self.clock.now(),
+                origin: self.id,
+                event: event.clone(),
+            };
+            if let Err(e) = envelope_send.send(envelope) {
+                error!(target: &self.log_target,
+                    "Drone '{}' failed to send event envelope: {}",
+                    self.id, e
+                );
+            }
+        }
+
+        if let Err(e) = self.controller_send.send(event) {
+            error!(target: &self.log_target,
+                "Drone '{}' failed to send event to controller: {}",
+                self.id, e
+            );
+        }
+    }
+
+    fn send_event(&self, event: RustDroneEvent) {
+        if let Some(event_send) = &self.event_send {
+            if let Err(e) = event_send.send(event) {
+                error!(target: &self.log_target,
+                    "Drone '{}' failed to send event: {}",
+                    self.id, e
+                );
+            }
+        }
+    }
+
+    fn flush_pending_packets(&mut self) {
+        // packets still buffered, being processed or in transit are not lost, handle them right away
+        for packet in self.paused_packets.take().into_iter().flatten() {
+            self.handle_packet(packet);
+        }
+        while let Some(packet) = self
+            .rate_limiter
+            .as_mut()
+            .and_then(|rate_limiter| rate_limiter.queue.pop_front())
+        {
+            self.handle_packet(packet);
+        }
+        while let Some((_, packet)) = self.delayed_packets.pop_front() {
+            self.handle_packet(packet);
+        }
+        while let Some(p) = self.packets_in_transit.pop_front() {
+            self.deliver_packet(&p.channel, p.next_hop, p.packet);
+        }
+        self.release_held_packet();
+    }
+
+    fn drain_packets(&mut self, timeout: Option<Duration>) {
+        self.flush_pending_packets();
+
+        let deadline = match timeout {
+            Some(timeout) => self.clock.at(self.clock.now() + timeout),
+            None => never(),
+        };
+        // neighbours keep connecting and disconnecting while the packets in flight are drained
+        let mut controller_recv = self.controller_recv.clone();
+
+        trace!(target: &self.log_target, "Drone '{}' is crashing state, waiting for Reciver to be closed", self.id);
+        loop {
+            select_biased! {
+                recv(controller_recv) -> command => {
+                    if let Ok(command) = command {
+                        self.handle_command_while_crashing(command);
+                    } else {
+                        debug!(target: &self.log_target, "Drone '{}' controller channel closed", self.id);
+                        controller_recv = never();
+                    }
+                },
+                recv(self.packet_recv) -> packet => {
+                    if let Ok(packet) = packet {
+                        self.handle_packet(packet);
+                        self.flush_pending_packets();
+                    } else {
+                        debug!(target: &self.log_target, "Drone '{}' Reciver closed, stopping", self.id);
+                        break;
+                    }
+                },
+                recv(deadline) -> _ => {
+                    warn!(target: &self.log_target,
+                        "Drone '{}' stopped draining after {:?}, dropping {} pending packets",
+                        self.id, timeout, self.packet_recv.len()
+                    );
+                    break;
+                },
+            }
+        }
+    }
+
+    fn handle_command_while_crashing(&mut self, command: DroneCommand) {
+        match command {
+            DroneCommand::Crash => {
+                debug!(target: &self.log_target, "Drone '{}' is already crashing", self.id);
+            }
+            command => {
+                self.handle_command(command);
+            }
+        }
+    }
+
+    fn receive_burst(&mut self, packet: Packet) {
+        // control packets waiting behind a burst of fragments are handled first,
+        // so acknowledgements don't starve under heavy load
+        let (control_packets, fragments): (Vec<_>, Vec<_>) = std::iter::once(packet)
+            .chain(self.packet_recv.try_iter().take(MAX_PACKET_BURST - 1))
+            .partition(|p| !matches!(p.pack_type, PacketType::MsgFragment(_)));
+
+        for packet in control_packets.into_iter().chain(fragments) {
+            // the rest of the burst can't be forwarded anymore, turn it away right now
+            if self.is_out_of_energy() {
+                self.return_nack(&packet, NackType::ErrorInRouting(self.id));
+                continue;
+            }
+            self.receive_packet(packet);
+        }
+    }
+
+    fn is_out_of_energy(&self) -> bool {
+        self.energy.as_ref().is_some_and(|e| e.energy == 0)
+    }
+
+    fn receive_packet(&mut self, packet: Packet) {
+        if let Some(paused_packets) = &mut self.paused_packets {
+            if paused_packets.len() < self.pause_buffer_capacity {
+                paused_packets.push_back(packet);
+            } else {
+                warn!(target: &self.log_target,
+                    "Drone '{}' is paused and its buffer is full, dropping packet",
+                    self.id
+                );
+                self.return_dropped_packet(packet);
+            }
+            return;
+        }
+
+        if let Some(rate_limiter) = &mut self.rate_limiter {
+            let now = self.clock.now();
+            // packets already waiting keep their turn
+            if !rate_limiter.queue.is_empty() || !rate_limiter.try_take(now) {
+                if rate_limiter.queue.len() < rate_limiter.limit.queue_capacity {
+                    rate_limiter.queue.push_back(packet);
+                } else {
+                    self.drop_rate_limited_packet(packet);
+                }
+                return;
+            }
+        }
+
+        self.process_packet(packet);
+    }
+
+    fn handle_rate_limited_packets(&mut self) {
+        let now = self.clock.now();
+
+        while let Some(rate_limiter) = &mut self.rate_limiter {
+            if rate_limiter.queue.is_empty() || !rate_limiter.try_take(now) {
+                break;
+            }
+            if let Some(packet) = rate_limiter.queue.pop_front() {
+                self.process_packet(packet);
+            }
+        }
+    }
+
+    fn drop_rate_limited_packet(&mut self, packet: Packet) {
+        warn!(target: &self.log_target,
+            "Drone '{}' is over its rate limit and its queue is full, dropping packet",
+            self.id
+        );
+        self.stats.rate_limited += 1;
+        self.send_event(RustDroneEvent::RateLimited(self.id, packet.clone()));
+        self.return_dropped_packet(packet);
+    }
+
+    /// Tell the sender about a packet dropped before being routed and report the drop,
+    /// control packets must not be lost and are shortcut to the controller instead
+    fn return_dropped_packet(&mut self, packet: Packet) {
+        self.return_nack(&packet, NackType::Dropped);
+
+        // a shortcut packet is delivered by the controller, it was not dropped
+        if matches!(
+            packet.pack_type,
+            PacketType::MsgFragment(_) | PacketType::FloodRequest(_)
+        ) {
+            self.send_controller_event(DroneEvent::PacketDropped(packet));
+        }
+    }
+
+    fn process_packet(&mut self, packet: Packet) {
+        let processing_delay = match &self.processing_delay {
+            Some(processing_delay) => processing_delay,
+            None => {
+                self.handle_packet(packet);
+                return;
+            }
+        };
+
+        // packets are processed one at a time, so each one waits for the previous to be done
+        let now = self.clock.now();
+        let start = match self.delayed_packets.back() {
+            Some((ready_at, _)) => (*ready_at).max(now),
+            None => now,
+        };
+        let delay = processing_delay.sample(&mut *self.rng);
+
+        trace!(target: &self.log_target,
+            "Drone '{}' processing packet for {:?}",
+            self.id, delay
+        );
+        self.delayed_packets.push_back((start + delay, packet));
+    }
+
+    fn handle_delayed_packets(&mut self) {
+        let now = self.clock.now();
+
+        while self
+            .delayed_packets
+            .front()
+            .is_some_and(|(ready_at, _)| *ready_at <= now)
+        {
+            if let Some((_, packet)) = self.delayed_packets.pop_front() {
+                self.handle_packet(packet);
+            }
+        }
+    }
+
+    /// Packets waiting to be handled or delivered
+    fn queue_depth(&self) -> usize {
+        self.packet_recv.len()
+            + self.rate_limiter.as_ref().map_or(0, |r| r.queue.len())
+            + self.delayed_packets.len()
+            + self.packets_in_transit.len()
+            + self.paused_packets.as_ref().map_or(0, VecDeque::len)
+            + usize::from(self.held_packet.is_some())
+    }
+
+    fn send_heartbeat(&mut self) {
+        let (interval, due_at) = match (self.heartbeat_interval, self.next_heartbeat) {
+            (Some(interval), Some(due_at)) => (interval, due_at),
+            _ => return,
+        };
+        let now = self.clock.now();
+        if now < due_at {
+            return;
+        }
+
+        self.send_event(RustDroneEvent::Heartbeat(
+            self.id,
+            Heartbeat {
+                uptime: now - self.started_at,
+                queue_depth: self.queue_depth(),
+            },
+        ));
+
+        // heartbeats missed while busy are skipped rather than sent all at once
+        let mut next_heartbeat = due_at + interval;
+        if next_heartbeat <= now {
+            next_heartbeat = now + interval;
+        }
+        self.next_heartbeat = Some(next_heartbeat);
+    }
+
+    fn deliver_packets_in_transit(&mut self) {
+        let now = self.clock.now();
+
+        while self
+            .packets_in_transit
+            .front()
+            .is_some_and(|p| p.arrive_at <= now)
+        {
+            if let Some(p) = self.packets_in_transit.pop_front() {
+                self.deliver_packet(&p.channel, p.next_hop, p.packet);
+            }
+        }
+
+        // no packet came to overtake the one held back, stop waiting for it
+        if self
+            .held_packet
+            .as_ref()
+            .is_some_and(|p| p.arrive_at <= now)
+        {
+            self.release_held_packet();
+        }
+    }
+
+    fn handle_packet(&mut self, mut packet: Packet) {
         trace!(target: &self.log_target,
             "Drone '{}' on thread '{}' with state '{:?}' recived packet: {:?}",
             self.id,
@@ -112,6 +758,16 @@ impl RustDrone {
             packet
         );
 
+        let id = self.id;
+        if self
+            .hooks
+            .iter_mut()
+            .any(|hook| hook.on_packet(id, &mut packet) == HookVerdict::Veto)
+        {
+            debug!(target: &self.log_target, "Drone '{}' packet vetoed by a hook", self.id);
+            return;
+        }
+
         // drone is crashing, ignore all packets
         if matches!(self.state, DroneState::Crashing) {
             match packet.pack_type {
@@ -131,6 +787,7 @@ impl RustDrone {
                     None => {
                         // we received a packet with no current hop
                         error!(target: &self.log_target, "Recived packet with no current hop");
+                        self.handle_malformed_packet(packet);
                         return;
                     }
                 };
@@ -175,16 +832,135 @@ impl RustDrone {
             DroneCommand::SetPacketDropRate(pdr) => {
                 info!(target: &self.log_target, "Drone '{}' set PDR to {}", self.id, pdr);
                 self.pdr = pdr;
+                self.drop_model.set_pdr(pdr);
                 CommandResult::Ok
             }
             DroneCommand::Crash => {
                 info!(target: &self.log_target, "Drone '{}' recived crash", self.id);
                 self.state = DroneState::Crashing;
+                self.send_event(RustDroneEvent::Crashing(self.id));
                 CommandResult::Quit
             }
         }
     }
 
+    fn handle_malformed_packet(&mut self, packet: Packet) {
+        self.stats.malformed_packets += 1;
+        match self.malformed_packet_policy {
+            MalformedPacketPolicy::Drop => {
+                debug!(target: &self.log_target, "Drone '{}' dropping malformed packet", self.id);
+            }
+            MalformedPacketPolicy::Report => {
+                self.send_event(RustDroneEvent::MalformedPacket(self.id, packet));
+            }
+            // there is no way to route anything back to the sender, only the controller can help
+            MalformedPacketPolicy::Shortcut => {
+                debug!(target: &self.log_target,
+                    "Drone '{}' sending malformed packet to the controller",
+                    self.id
+                );
+                self.send_controller_event(DroneEvent::ControllerShortcut(packet.clone()));
+                self.send_event(RustDroneEvent::MalformedPacket(self.id, packet));
+            }
+        }
+    }
+
+    fn handle_rust_command(&mut self, command: RustDroneCommand) -> CommandResult {
+        match command {
+            RustDroneCommand::QueryNeighbours => {
+                let mut neighbours = self.packet_send.keys().copied().collect::<Vec<_>>();
+                neighbours.sort();
+
+                debug!(target: &self.log_target,
+                    "Drone '{}' reporting neighbours {:?}",
+                    self.id, neighbours
+                );
+                self.send_event(RustDroneEvent::Neighbours(self.id, neighbours));
+                CommandResult::Ok
+            }
+            RustDroneCommand::QuerySnapshot => {
+                let mut neighbours = self.packet_send.keys().copied().collect::<Vec<_>>();
+                neighbours.sort();
+
+                let snapshot = DroneSnapshot {
+                    neighbours,
+                    pdr: self.pdr,
+                    status: if self.paused_packets.is_some() {
+                        DroneStatus::Paused
+                    } else {
+                        DroneStatus::Running
+                    },
+                    queue_length: self.queue_depth(),
+                };
+                debug!(target: &self.log_target,
+                    "Drone '{}' reporting snapshot {:?}",
+                    self.id, snapshot
+                );
+                self.send_event(RustDroneEvent::Snapshot(self.id, snapshot));
+                CommandResult::Ok
+            }
+            RustDroneCommand::Sync(sync_id) => {
+                // DroneCommands are always picked first, the ones sent before were applied
+                debug!(target: &self.log_target, "Drone '{}' synced '{}'", self.id, sync_id);
+                self.send_event(RustDroneEvent::Synced(self.id, sync_id));
+                CommandResult::Ok
+            }
+            RustDroneCommand::Crash(crash_mode) => {
+                self.crash_mode = crash_mode;
+                self.handle_command(DroneCommand::Crash)
+            }
+            RustDroneCommand::SetLinkQuality(node_id, link_quality) => {
+                info!(target: &self.log_target,
+                    "Drone '{}' set quality of link to '{}' to {:?}",
+                    self.id, node_id, link_quality
+                );
+                self.link_quality.insert(node_id, link_quality);
+                CommandResult::Ok
+            }
+            RustDroneCommand::Pause => {
+                info!(target: &self.log_target, "Drone '{}' paused", self.id);
+                self.paused_packets.get_or_insert_with(VecDeque::new);
+                self.send_event(RustDroneEvent::Paused(self.id));
+                CommandResult::Ok
+            }
+            RustDroneCommand::Resume => {
+                let paused_packets = self.paused_packets.take().unwrap_or_default();
+                info!(target: &self.log_target,
+                    "Drone '{}' resumed with {} buffered packets",
+                    self.id,
+                    paused_packets.len()
+                );
+                self.send_event(RustDroneEvent::Resumed(self.id));
+                for packet in paused_packets {
+                    self.receive_packet(packet);
+                }
+                CommandResult::Ok
+            }
+            RustDroneCommand::ReportStats => {
+                debug!(target: &self.log_target,
+                    "Drone '{}' reporting stats {:?}",
+                    self.id, self.stats
+                );
+                self.send_event(RustDroneEvent::Stats(self.id, self.stats.clone()));
+                CommandResult::Ok
+            }
+            RustDroneCommand::ResetStats => {
+                info!(target: &self.log_target, "Drone '{}' reset its stats", self.id);
+                self.stats = DroneStats::default();
+                self.tracked_sessions.clear();
+                CommandResult::Ok
+            }
+            RustDroneCommand::SetLinkPdr(node_id, pdr) => {
+                info!(target: &self.log_target,
+                    "Drone '{}' set PDR of link to '{}' to {}",
+                    self.id, node_id, pdr
+                );
+                self.link_quality.entry(node_id).or_default().pdr = Some(pdr);
+                CommandResult::Ok
+            }
+        }
+    }
+
     fn get_current_hop(packet: &Packet) -> Option<NodeId> {
         packet
             .routing_header
@@ -193,6 +969,28 @@ impl RustDrone {
             .cloned()
     }
 
+    fn has_cyclic_route(packet: &Packet) -> bool {
+        let remaining_hops = match packet
+            .routing_header
+            .hops
+            .get(packet.routing_header.hop_index..)
+        {
+            Some(remaining_hops) => remaining_hops,
+            None => return false,
+        };
+
+        // the current hop appears again later in the route
+        let comes_back = remaining_hops
+            .split_first()
+            .is_some_and(|(current_hop, next_hops)| next_hops.contains(current_hop));
+
+        // two nodes sending the packet back and forth (A, B, A) or to themselves (A, A)
+        let bounces = remaining_hops.windows(2).any(|w| w[0] == w[1])
+            || remaining_hops.windows(3).any(|w| w[0] == w[2]);
+
+        comes_back || bounces
+    }
+
     fn get_next_hop(packet: &Packet) -> Option<NodeId> {
         packet
             .routing_header
@@ -201,43 +999,83 @@ impl RustDrone {
             .cloned()
     }
 
-    fn deliver_packet(&mut self, channel: &Sender<Packet>, sender_id: NodeId, packet: Packet) {
+    fn deliver_packet(&mut self, channel: &Sender<Packet>, sender_id: NodeId, mut packet: Packet) {
+        let id = self.id;
+        if self
+            .hooks
+            .iter_mut()
+            .any(|hook| hook.on_forward(id, sender_id, &mut packet) == HookVerdict::Veto)
+        {
+            debug!(target: &self.log_target,
+                "Drone '{}' packet to '{}' vetoed by a hook",
+                self.id, sender_id
+            );
+            return;
+        }
+
         if let Err(e) = channel.try_send(packet.clone()) {
-            // if error indicates that the receiver has been dropped, we should remove the sender
-            if matches!(e, crossbeam::channel::TrySendError::Disconnected(_)) {
-                if self.packet_send.remove(&sender_id).is_none() {
-                    error!(target: &self.log_target,
-                        "Drone '{}' tried to disconnect from '{}', but it was not connected",
+            // the packet was already moved to the next hop, any NACK has to start from here
+            let mut returned_packet = packet.clone();
+            returned_packet.routing_header.hop_index =
+                returned_packet.routing_header.hop_index.saturating_sub(1);
+
+            match e {
+                // the receiver has been dropped, we should remove the sender
+                TrySendError::Disconnected(_) => {
+                    if self.packet_send.remove(&sender_id).is_none() {
+                        error!(target: &self.log_target,
+                            "Drone '{}' tried to disconnect from '{}', but it was not connected",
+                            self.id, sender_id
+                        );
+                    }
+                    warn!(target: &self.log_target,
+                        "Drone '{}' disconnected from '{}' due to channel disconnected",
                         self.id, sender_id
                     );
+                    self.return_nack(&returned_packet, NackType::ErrorInRouting(sender_id));
+                }
+                // the receiver is congested, tell the sender its packet was dropped
+                TrySendError::Full(_) => {
+                    warn!(target: &self.log_target,
+                        "Drone '{}' failed to send packet to '{}', its queue is full",
+                        self.id, sender_id
+                    );
+                    self.stats.queue_overflows += 1;
+                    self.send_event(RustDroneEvent::QueueOverflow(
+                        self.id,
+                        sender_id,
+                        packet.clone(),
+                    ));
+                    self.return_nack(&returned_packet, NackType::Dropped);
                 }
-                warn!(target: &self.log_target,
-                    "Drone '{}' disconnected from '{}' due to channel disconnected",
-                    self.id, sender_id
-                );
-                self.return_nack(&packet, NackType::ErrorInRouting(sender_id));
-            } else {
-                error!(target: &self.log_target,
-                    "Drone '{}' failed to send packet to channel: {}",
-                    self.id, e
-                );
             }
 
-            if let Err(e) = self.controller_send.send(DroneEvent::PacketDropped(packet)) {
-                error!(target: &self.log_target,
-                    "Drone '{}' failed to send PacketDropped event to controller: {}",
-                    self.id, e
-                );
+            self.send_controller_event(DroneEvent::PacketDropped(packet));
+        } else {
+            if matches!(packet.pack_type, PacketType::MsgFragment(_)) {
+                self.stats.fragments_forwarded += 1;
             }
-        } else if let Err(e) = self.controller_send.send(DroneEvent::PacketSent(packet)) {
-            error!(target: &self.log_target,
-                "Drone '{}' failed to send PacketSent event to controller: {}",
-                self.id, e
-            );
+            if let Some(energy) = &mut self.energy {
+                energy.energy = energy.energy.saturating_sub(energy.forward_cost);
+            }
+            self.send_controller_event(DroneEvent::PacketSent(packet));
         }
     }
 
     fn route_packet(&mut self, mut packet: Packet) {
+        self.forget_nacked_fragment(&packet);
+
+        // a drone out of energy can't send anything, like a crashed one it tells the sender
+        // or, for control packets, the controller
+        if self.is_out_of_energy() {
+            warn!(target: &self.log_target,
+                "Drone '{}' is out of energy, not forwarding packet",
+                self.id
+            );
+            self.return_nack(&packet, NackType::ErrorInRouting(self.id));
+            return;
+        }
+
         // check if the packet has another hop
         let next_hop = match Self::get_next_hop(&packet) {
             Some(next_hop) => next_hop,
@@ -256,6 +1094,35 @@ impl RustDrone {
             }
         };
 
+        // refuse routes that come back to us or bounce between two nodes, except for
+        // flood responses, which retrace the flood and loop wherever the flood did
+        if !matches!(packet.pack_type, PacketType::FloodResponse(_))
+            && Self::has_cyclic_route(&packet)
+        {
+            warn!(target: &self.log_target,
+                "Drone '{}' received packet with a cyclic route {:?}",
+                self.id, packet.routing_header.hops
+            );
+            self.send_event(RustDroneEvent::CyclicRoute(self.id, packet.clone()));
+            self.return_nack(&packet, NackType::ErrorInRouting(next_hop));
+            return;
+        }
+
+        // refuse absurdly long routes, most likely generated by a buggy client
+        if self
+            .max_hops
+            .is_some_and(|max_hops| packet.routing_header.hops.len() > max_hops)
+        {
+            warn!(target: &self.log_target,
+                "Drone '{}' received packet with a route of {} hops, longer than allowed",
+                self.id,
+                packet.routing_header.hops.len()
+            );
+            self.send_event(RustDroneEvent::RouteTooLong(self.id, packet.clone()));
+            self.return_nack(&packet, NackType::ErrorInRouting(next_hop));
+            return;
+        }
+
         // check if the next hop is in the list of connected nodes
         let forward_channel = match self.packet_send.get(&next_hop) {
             Some(sender) => sender.clone(),
@@ -270,28 +1137,332 @@ impl RustDrone {
             }
         };
 
+        if self.is_duplicate_fragment(&packet) {
+            debug!(target: &self.log_target,
+                "Drone '{}' dropping duplicate fragment of session {}",
+                self.id, packet.session_id
+            );
+            self.send_event(RustDroneEvent::DuplicateSuppressed(self.id, packet));
+            return;
+        }
+
+        if self.should_drop_selectively(&packet) {
+            warn!(target: &self.log_target,
+                "Drone '{}' is byzantine, silently dropping fragment to '{:?}'",
+                self.id,
+                packet.routing_header.hops.last()
+            );
+            self.send_event(RustDroneEvent::Byzantine(
+                self.id,
+                ByzantineAction::SilentlyDropped,
+                packet,
+            ));
+            return;
+        }
+
         // we are connected to the next hop, now we might want to drop the packet only if it's a fragment
-        if !matches!(packet.pack_type, PacketType::MsgFragment(_))
-            || rand::rng().random_range(0.0..1.0) >= self.pdr
-        {
+        let dropped = match &packet.pack_type {
+            PacketType::MsgFragment(fragment) => {
+                let session_stats = self.session_stats(packet.session_id);
+                session_stats.fragments_seen += 1;
+                session_stats.last_fragment_index = fragment.fragment_index;
+
+                match self.link_quality.get(&next_hop).and_then(|l| l.pdr) {
+                    Some(pdr) => self.rng.random_range(0.0..1.0) < pdr,
+                    None => self.drop_model.should_drop(fragment, &mut *self.rng),
+                }
+            }
+            _ => self.should_drop_control_packet(&packet),
+        };
+
+        if !dropped {
             // luck is on our side, we can forward the packet
             debug!(target: &self.log_target, "Drone '{}' forwarding packet to '{}'", self.id, next_hop);
+            self.remember_fragment(&packet);
             packet.routing_header.hop_index += 1;
 
-            self.deliver_packet(&forward_channel, next_hop, packet)
+            if self.byzantine.is_some() {
+                self.misbehave(&forward_channel, next_hop, packet)
+            } else {
+                self.forward_packet(&forward_channel, next_hop, packet)
+            }
         } else {
             // drop the packet
             info!(target: &self.log_target, "Packet has been dropped from node '{}'", self.id);
-            if let Err(e) = self
-                .controller_send
-                .send(DroneEvent::PacketDropped(packet.clone()))
+            self.send_controller_event(DroneEvent::PacketDropped(packet.clone()));
+
+            // a dropped control packet must stay lost, a NACK would shortcut it to the controller
+            if matches!(packet.pack_type, PacketType::MsgFragment(_)) {
+                self.stats.fragments_dropped += 1;
+                self.session_stats(packet.session_id).fragments_dropped += 1;
+                self.return_nack(&packet, NackType::Dropped);
+            }
+        }
+    }
+
+    fn session_stats(&mut self, session_id: u64) -> &mut SessionStats {
+        if !self.stats.sessions.contains_key(&session_id) {
+            if self.tracked_sessions.len() >= MAX_TRACKED_SESSIONS {
+                if let Some(oldest) = self.tracked_sessions.pop_front() {
+                    self.stats.sessions.remove(&oldest);
+                }
+            }
+            self.tracked_sessions.push_back(session_id);
+        }
+        self.stats.sessions.entry(session_id).or_default()
+    }
+
+    fn is_duplicate_fragment(&self, packet: &Packet) -> bool {
+        match (&self.recent_fragments, &packet.pack_type) {
+            (Some(recent_fragments), PacketType::MsgFragment(fragment)) => recent_fragments
+                .seen
+                .contains(&(packet.session_id, fragment.fragment_index)),
+            _ => false,
+        }
+    }
+
+    fn remember_fragment(&mut self, packet: &Packet) {
+        if let (Some(recent_fragments), PacketType::MsgFragment(fragment)) =
+            (&mut self.recent_fragments, &packet.pack_type)
+        {
+            recent_fragments.insert((packet.session_id, fragment.fragment_index));
+        }
+    }
+
+    /// A NACK means the sender is going to send the fragment again, which is not a duplicate
+    fn forget_nacked_fragment(&mut self, packet: &Packet) {
+        if let (Some(recent_fragments), PacketType::Nack(nack)) =
+            (&mut self.recent_fragments, &packet.pack_type)
+        {
+            recent_fragments.remove(&(packet.session_id, nack.fragment_index));
+        }
+    }
+
+    fn should_drop_selectively(&mut self, packet: &Packet) -> bool {
+        let selective_forwarding = match &self.selective_forwarding {
+            Some(selective_forwarding) => selective_forwarding,
+            None => return false,
+        };
+
+        matches!(packet.pack_type, PacketType::MsgFragment(_))
+            && packet
+                .routing_header
+                .hops
+                .last()
+                .is_some_and(|destination| selective_forwarding.targets.contains(destination))
+            && self.rng.random_range(0.0..1.0) < selective_forwarding.drop_rate
+    }
+
+    fn misbehave(&mut self, channel: &Sender<Packet>, next_hop: NodeId, mut packet: Packet) {
+        let byzantine = match &self.byzantine {
+            Some(byzantine) => byzantine.clone(),
+            None => return,
+        };
+        // the packet as it was received, for the NACKs
+        let mut received_packet = packet.clone();
+        received_packet.routing_header.hop_index -= 1;
+
+        let mut actions = Vec::new();
+
+        match &mut packet.pack_type {
+            // only the bytes within the length are read, flipping the padding would go unnoticed
+            PacketType::MsgFragment(fragment)
+                if fragment.length > 0
+                    && self.rng.random_range(0.0..1.0) < byzantine.corrupt_payload =>
             {
-                error!(target: &self.log_target,
-                    "Drone '{}' failed to send PacketDropped event: {}",
-                    self.id, e
-                );
+                let length = (fragment.length as usize).min(fragment.data.len());
+                let i = self.rng.random_range(0..length);
+                fragment.data[i] ^= self.rng.random_range(1..=u8::MAX);
+                actions.push(ByzantineAction::CorruptedPayload);
+            }
+            PacketType::FloodResponse(flood_response) => {
+                if self.rng.random_range(0.0..1.0) < byzantine.forge_path_trace {
+                    // claim a link to a node that is not part of the trace
+                    let fake_id = loop {
+                        let id = self.rng.random::<NodeId>();
+                        if !flood_response.path_trace.iter().any(|(n, _)| *n == id) {
+                            break id;
+                        }
+                    };
+                    let position = flood_response
+                        .path_trace
+                        .iter()
+                        .position(|(n, _)| *n == self.id)
+                        .map_or(flood_response.path_trace.len(), |i| i + 1);
+                    flood_response
+                        .path_trace
+                        .insert(position, (fake_id, NodeType::Drone));
+                    actions.push(ByzantineAction::ForgedPathTrace);
+                }
+
+                let len = flood_response.path_trace.len();
+                if len > 1 && self.rng.random_range(0.0..1.0) < byzantine.reorder_path_trace {
+                    let a = self.rng.random_range(0..len);
+                    let b = (a + self.rng.random_range(1..len)) % len;
+                    flood_response.path_trace.swap(a, b);
+                    actions.push(ByzantineAction::ReorderedPathTrace);
+                }
+            }
+            _ => {}
+        }
+
+        let len = packet.routing_header.hops.len();
+        if len > 1 && self.rng.random_range(0.0..1.0) < byzantine.corrupt_hop_index {
+            let hop_index = &mut packet.routing_header.hop_index;
+            *hop_index = (*hop_index + self.rng.random_range(1..len)) % len;
+            actions.push(ByzantineAction::CorruptedHopIndex);
+        }
+
+        for action in actions {
+            warn!(target: &self.log_target,
+                "Drone '{}' is byzantine, {:?} on packet to '{}'",
+                self.id, action, next_hop
+            );
+            self.send_event(RustDroneEvent::Byzantine(self.id, action, packet.clone()));
+        }
+
+        if self.rng.random_range(0.0..1.0) < byzantine.duplicate {
+            warn!(target: &self.log_target,
+                "Drone '{}' is byzantine, duplicating packet to '{}'",
+                self.id, next_hop
+            );
+            self.send_event(RustDroneEvent::Byzantine(
+                self.id,
+                ByzantineAction::Duplicated,
+                packet.clone(),
+            ));
+            self.forward_packet(channel, next_hop, packet.clone());
+        }
+
+        let bogus_nack = matches!(packet.pack_type, PacketType::MsgFragment(_))
+            && self.rng.random_range(0.0..1.0) < byzantine.bogus_nack;
+
+        self.forward_packet(channel, next_hop, packet.clone());
+
+        if bogus_nack {
+            // claim the fragment was dropped even though it was forwarded
+            warn!(target: &self.log_target,
+                "Drone '{}' is byzantine, sending a bogus NACK for packet to '{}'",
+                self.id, next_hop
+            );
+            self.send_event(RustDroneEvent::Byzantine(
+                self.id,
+                ByzantineAction::BogusNack,
+                packet,
+            ));
+            self.return_nack(&received_packet, NackType::Dropped);
+        }
+    }
+
+    fn forward_packet(&mut self, channel: &Sender<Packet>, next_hop: NodeId, packet: Packet) {
+        let fault_injection = match &self.fault_injection {
+            Some(fault_injection) => fault_injection.clone(),
+            None => return self.send_on_link(channel, next_hop, packet),
+        };
+
+        if self.rng.random_range(0.0..1.0) < fault_injection.duplicate {
+            self.send_event(RustDroneEvent::FaultInjected(
+                self.id,
+                Fault::Duplicated,
+                packet.clone(),
+            ));
+            self.send_on_link(channel, next_hop, packet.clone());
+        }
+
+        if self.held_packet.is_none() && self.rng.random_range(0.0..1.0) < fault_injection.reorder {
+            trace!(target: &self.log_target,
+                "Drone '{}' holding back packet to '{}' to reorder it",
+                self.id, next_hop
+            );
+            self.send_event(RustDroneEvent::FaultInjected(
+                self.id,
+                Fault::Reordered,
+                packet.clone(),
+            ));
+            self.held_packet = Some(PacketInTransit {
+                arrive_at: self.clock.now() + MAX_REORDER_HOLD,
+                next_hop,
+                channel: channel.clone(),
+                packet,
+            });
+            return;
+        }
+
+        self.send_on_link(channel, next_hop, packet);
+        self.release_held_packet();
+    }
+
+    fn release_held_packet(&mut self) {
+        if let Some(p) = self.held_packet.take() {
+            self.send_on_link(&p.channel, p.next_hop, p.packet);
+        }
+    }
+
+    fn send_on_link(&mut self, channel: &Sender<Packet>, next_hop: NodeId, packet: Packet) {
+        let link_quality = match self.link_quality.get(&next_hop) {
+            Some(l) if !l.latency.is_zero() || l.bandwidth.is_some() => l,
+            _ => return self.deliver_packet(channel, next_hop, packet),
+        };
+
+        // fragments keep the link busy while their payload is transmitted and leave in order,
+        // control packets are tiny and only pay the latency
+        let now = self.clock.now();
+        let sent_at = match (&packet.pack_type, link_quality.bandwidth) {
+            (PacketType::MsgFragment(fragment), Some(bandwidth)) if bandwidth > 0 => {
+                let transmission_time =
+                    Duration::from_secs_f64(fragment.length as f64 * 8.0 / bandwidth as f64);
+                let sent_at = self
+                    .link_busy_until
+                    .get(&next_hop)
+                    .map_or(now, |busy_until| (*busy_until).max(now))
+                    + transmission_time;
+                self.link_busy_until.insert(next_hop, sent_at);
+                sent_at
             }
-            self.return_nack(&packet, NackType::Dropped);
+            _ => now,
+        };
+        let arrive_at = sent_at + link_quality.latency;
+
+        trace!(target: &self.log_target,
+            "Drone '{}' packet to '{}' in transit for {:?}",
+            self.id, next_hop, arrive_at - now
+        );
+        let position = self
+            .packets_in_transit
+            .partition_point(|p| p.arrive_at <= arrive_at);
+        self.packets_in_transit.insert(
+            position,
+            PacketInTransit {
+                arrive_at,
+                next_hop,
+                channel: channel.clone(),
+                packet,
+            },
+        );
+    }
+
+    fn should_drop_control_packet(&mut self, packet: &Packet) -> bool {
+        let control_pdr = match &self.control_pdr {
+            Some(control_pdr) => control_pdr,
+            None => return false,
+        };
+
+        let pdr = match packet.pack_type {
+            PacketType::Ack(_) => control_pdr.ack,
+            PacketType::Nack(_) => control_pdr.nack,
+            PacketType::FloodRequest(_) => control_pdr.flood_request,
+            PacketType::FloodResponse(_) => control_pdr.flood_response,
+            PacketType::MsgFragment(_) => return false,
+        };
+
+        if self.rng.random_range(0.0..1.0) < pdr {
+            warn!(target: &self.log_target,
+                "Drone '{}' is dropping a control packet, this is NOT protocol conformant",
+                self.id
+            );
+            true
+        } else {
+            false
         }
     }
 
@@ -304,22 +1475,27 @@ impl RustDrone {
         );
 
         match &packet.pack_type {
+            PacketType::FloodRequest(_) => {
+                // flood requests carry no route back, losing one is harmless for the flood
+                debug!(target: &self.log_target,
+                    "Drone '{}' not returning NACK for FloodRequest",
+                    self.id
+                );
+            }
             PacketType::Ack(_) | PacketType::Nack(_) | PacketType::FloodResponse(_) => {
                 warn!(target: &self.log_target,
                     "Drone '{}' returning NACK to sender for Ack, Nack or FloodResponse",
                     self.id
                 );
                 // send shortcut to controller if the packet is Ack, Nack or FloodResponse
-                if self
-                    .controller_send
-                    .send(DroneEvent::ControllerShortcut(packet.clone()))
-                    .is_err()
-                {
-                    error!(target: &self.log_target,
-                        "Drone '{}' failed to send ControllerShortcut event to controller",
-                        self.id
-                    );
-                }
+                self.send_controller_event(DroneEvent::ControllerShortcut(packet.clone()));
+            }
+            // there is no way back without a current hop, the caller reports the packet as malformed
+            _ if Self::get_current_hop(packet).is_none() => {
+                debug!(target: &self.log_target,
+                    "Drone '{}' not returning NACK for packet with no current hop",
+                    self.id
+                );
             }
             _ => {
                 debug!(target: &self.log_target,
@@ -338,6 +1514,13 @@ impl RustDrone {
                     .cloned()
                     .collect();
 
+                match &nack_type {
+                    NackType::ErrorInRouting(_) => self.stats.nacks_error_in_routing += 1,
+                    NackType::DestinationIsDrone => self.stats.nacks_destination_is_drone += 1,
+                    NackType::Dropped => self.stats.nacks_dropped += 1,
+                    NackType::UnexpectedRecipient(_) => self.stats.nacks_unexpected_recipient += 1,
+                }
+
                 // build the NACK packet
                 let nack = Packet {
                     pack_type: PacketType::Nack(Nack {
@@ -372,23 +1555,12 @@ impl RustDrone {
             .map(|(id, _)| *id)
             .collect();
 
-        let sender = match self.packet_send.get(&neighbour) {
-            Some(sender) => sender.clone(),
-            None => {
-                error!(target: &self.log_target,
-                    "Drone '{}' tried to return flood response to '{}', but it was not connected to it",
-                    self.id, neighbour
-                );
-                return;
-            }
-        };
-
         let flood_response = Packet {
             pack_type: PacketType::FloodResponse(FloodResponse {
                 flood_id: flood_request.flood_id,
                 path_trace: flood_request.path_trace,
             }),
-            routing_header: SourceRoutingHeader { hops, hop_index: 1 },
+            routing_header: SourceRoutingHeader { hops, hop_index: 0 },
             session_id,
         };
 
@@ -397,31 +1569,43 @@ impl RustDrone {
             self.id,
             neighbour
         );
-        self.deliver_packet(&sender, neighbour, flood_response);
+        // route the response like any other packet, if the previous hop is no longer
+        // reachable this ends up in a ControllerShortcut instead of being lost
+        self.route_packet(flood_response);
     }
 
     fn handle_flood_request(&mut self, packet: Packet) {
+        if self.is_out_of_energy() {
+            debug!(target: &self.log_target,
+                "Drone '{}' is out of energy, ignoring flood request",
+                self.id
+            );
+            return;
+        }
+
+        if self.should_drop_control_packet(&packet) {
+            self.send_controller_event(DroneEvent::PacketDropped(packet));
+            return;
+        }
+
+        self.stats.floods_handled += 1;
+        if let Some(energy) = &mut self.energy {
+            energy.energy = energy.energy.saturating_sub(energy.flood_cost);
+        }
+
         let mut flood_request = match packet.pack_type {
             PacketType::FloodRequest(flood_request) => flood_request,
             _ => unreachable!(),
         };
 
-        let initializator_id = match flood_request.path_trace.first() {
-            Some(a) => a.0,
-            None => {
-                error!(target: &self.log_target,
-                    "Path trace in flood request {} is empty",
-                    flood_request.flood_id
-                );
-                return;
-            }
-        };
+        // floods are told apart by their initiator, different clients may pick the same flood id
+        let initiator_id = flood_request.initiator_id;
 
         trace!(target: &self.log_target,
             "Drone '{}' handling flood request with id '{}' from node '{}'",
             self.id,
             flood_request.flood_id,
-            initializator_id
+            initiator_id
         );
 
         let sender_id = match flood_request.path_trace.last() {
@@ -439,7 +1623,7 @@ impl RustDrone {
 
         if self
             .seen_flood_requests
-            .contains(&(initializator_id, flood_request.flood_id))
+            .contains(&(initiator_id, flood_request.flood_id))
         {
             // we have already seen this flood request
             debug!(target: &self.log_target,
@@ -451,23 +1635,28 @@ impl RustDrone {
             // never seen this flood request
             debug!(target: &self.log_target,
                 "Drone '{}' handling flood request with id '{}' from node '{}' for the first time",
-                self.id, flood_request.flood_id, initializator_id
+                self.id, flood_request.flood_id, initiator_id
             );
             self.seen_flood_requests
-                .insert((initializator_id, flood_request.flood_id));
+                .insert((initiator_id, flood_request.flood_id));
 
-            if self.packet_send.len() > 1 {
-                // we have more than one neighbour, we need to forward the flood request to all but one
+            // the sender might have been disconnected in the meantime, so it can't be
+            // counted among the neighbours
+            let neighbours = self
+                .packet_send
+                .iter()
+                .filter(|(neighbour, _)| **neighbour != sender_id)
+                .map(|(neighbour, sender)| (*neighbour, sender.clone()))
+                .collect::<Vec<_>>();
+
+            if !neighbours.is_empty() {
+                // we have other neighbours, we need to forward the flood request to all of them
                 debug!(target: &self.log_target,
-                    "Drone '{}' has more than one neighbour, forwarding flood request to all but '{}'",
+                    "Drone '{}' has other neighbours, forwarding flood request to all but '{}'",
                     self.id, sender_id
                 );
 
-                for (neighbour, sender) in self.packet_send.clone().iter() {
-                    if *neighbour == sender_id {
-                        continue;
-                    }
-
+                for (neighbour, sender) in neighbours.iter() {
                     trace!(target: &self.log_target,
                         "Drone '{}' forwarding flood request to '{}'",
                         self.id,
@@ -488,7 +1677,7 @@ impl RustDrone {
                     );
                 }
             } else {
-                // we have only one neighbour, we can return the flood response
+                // the sender is our only neighbour, we can return the flood response
                 debug!(target: &self.log_target,
                     "Drone '{}' has no other neighbour, returning a flood response to '{}'",
                     self.id, sender_id
@@ -0,0 +1,147 @@
+use rand::{Rng, RngCore};
+
+use wg_2024::packet::Fragment;
+
+/// Decides which of the fragments forwarded by a `RustDrone` get dropped
+pub trait DropModel: Send {
+    /// Returns `true` if the fragment should be dropped
+    fn should_drop(&mut self, fragment: &Fragment, rng: &mut dyn RngCore) -> bool;
+
+    /// Applies a new packet drop rate received with `DroneCommand::SetPacketDropRate`
+    fn set_pdr(&mut self, pdr: f32);
+}
+
+/// Drops every fragment with the same probability, as required by the protocol
+pub struct Uniform {
+    pdr: f32,
+}
+
+impl Uniform {
+    pub fn new(pdr: f32) -> Self {
+        Self { pdr }
+    }
+}
+
+impl DropModel for Uniform {
+    fn should_drop(&mut self, _fragment: &Fragment, rng: &mut dyn RngCore) -> bool {
+        rng.random_range(0.0..1.0) < self.pdr
+    }
+
+    fn set_pdr(&mut self, pdr: f32) {
+        self.pdr = pdr;
+    }
+}
+
+/// Drops fragments with a probability proportional to their `length`,
+/// a full fragment is dropped with probability `pdr`
+pub struct SizeDependent {
+    pdr: f32,
+}
+
+impl SizeDependent {
+    pub fn new(pdr: f32) -> Self {
+        Self { pdr }
+    }
+}
+
+impl DropModel for SizeDependent {
+    fn should_drop(&mut self, fragment: &Fragment, rng: &mut dyn RngCore) -> bool {
+        let pdr = self.pdr * fragment.length as f32 / fragment.data.len() as f32;
+        rng.random_range(0.0..1.0) < pdr
+    }
+
+    fn set_pdr(&mut self, pdr: f32) {
+        self.pdr = pdr;
+    }
+}
+
+/// Bursty losses: the link switches between a good and a bad state,
+/// each with its own drop rate
+pub struct GilbertElliott {
+    good_to_bad: f32,
+    bad_to_good: f32,
+    pdr_good: f32,
+    pdr_bad: f32,
+    bad: bool,
+}
+
+impl GilbertElliott {
+    /// `good_to_bad` and `bad_to_good` are the probabilities of switching state before each fragment
+    pub fn new(good_to_bad: f32, bad_to_good: f32, pdr_good: f32, pdr_bad: f32) -> Self {
+        Self {
+            good_to_bad,
+            bad_to_good,
+            pdr_good,
+            pdr_bad,
+            bad: false,
+        }
+    }
+}
+
+impl DropModel for GilbertElliott {
+    fn should_drop(&mut self, _fragment: &Fragment, rng: &mut dyn RngCore) -> bool {
+        let switch = if self.bad {
+            self.bad_to_good
+        } else {
+            self.good_to_bad
+        };
+        if rng.random_range(0.0..1.0) < switch {
+            self.bad = !self.bad;
+        }
+
+        let pdr = if self.bad {
+            self.pdr_bad
+        } else {
+            self.pdr_good
+        };
+        rng.random_range(0.0..1.0) < pdr
+    }
+
+    /// Only changes the drop rate of the bad state, the bursts keep their length
+    fn set_pdr(&mut self, pdr: f32) {
+        self.pdr_bad = pdr;
+    }
+}
+
+/// Deterministically drops every `n`-th fragment, never drops anything if `n` is 0
+pub struct EveryNth {
+    n: u64,
+    count: u64,
+}
+
+impl EveryNth {
+    pub fn new(n: u64) -> Self {
+        Self { n, count: 0 }
+    }
+}
+
+impl DropModel for EveryNth {
+    fn should_drop(&mut self, _fragment: &Fragment, _rng: &mut dyn RngCore) -> bool {
+        if self.n == 0 {
+            return false;
+        }
+
+        self.count += 1;
+        self.count.is_multiple_of(self.n)
+    }
+
+    /// Drops every `1 / pdr`-th fragment, rounded to the closest one
+    fn set_pdr(&mut self, pdr: f32) {
+        self.n = if pdr > 0.0 {
+            (1.0 / pdr).round() as u64
+        } else {
+            0
+        };
+        self.count = 0;
+    }
+}
+
+/// Drop rates for the packets the protocol requires to always be delivered,
+/// they are only applied by drones explicitly set up to be non conformant
+#[derive(Debug, Clone, Default)]
+pub struct ControlPdr {
+    pub ack: f32,
+    pub nack: f32,
+    pub flood_request: f32,
+    pub flood_response: f32,
+}
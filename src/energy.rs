@@ -0,0 +1,8 @@
+/// Energy of a battery powered drone, spent on every packet it forwards and every
+/// flood request it handles. The drone crashes once it runs out
+#[derive(Debug, Clone)]
+pub struct EnergyBudget {
+    pub energy: u64,
+    pub forward_cost: u64,
+    pub flood_cost: u64,
+}
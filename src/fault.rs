@@ -0,0 +1,16 @@
+/// Probabilities with which a `RustDrone` injects network faults on the packets it forwards.
+/// Unlike a byzantine drone, it never changes their content, it only messes with their delivery
+#[derive(Debug, Clone, Default)]
+pub struct FaultInjection {
+    /// Forward the packet twice
+    pub duplicate: f32,
+    /// Hold the packet back and forward it right after the next one
+    pub reorder: f32,
+}
+
+/// A fault injected on a packet, reported with `RustDroneEvent::FaultInjected`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    Duplicated,
+    Reordered,
+}
@@ -0,0 +1,30 @@
+use wg_2024::network::NodeId;
+use wg_2024::packet::Packet;
+
+/// What a `PacketHook` wants the drone to do with a packet
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HookVerdict {
+    /// Go on as usual, with the packet as the hook left it
+    Pass,
+    /// Silently discard the packet
+    Veto,
+}
+
+/// Custom logic run by a `RustDrone` on the packets it handles, to inspect, change or discard
+/// them without touching the drone itself. Both methods let every packet pass by default
+pub trait PacketHook: Send {
+    /// Called with every packet the drone is about to handle
+    fn on_packet(&mut self, _drone_id: NodeId, _packet: &mut Packet) -> HookVerdict {
+        HookVerdict::Pass
+    }
+
+    /// Called with every packet the drone is about to send to `next_hop`
+    fn on_forward(
+        &mut self,
+        _drone_id: NodeId,
+        _next_hop: NodeId,
+        _packet: &mut Packet,
+    ) -> HookVerdict {
+        HookVerdict::Pass
+    }
+}
@@ -1,4 +1,16 @@
+pub mod builder;
+pub mod byzantine;
+pub mod clock;
+pub mod controller;
+pub mod delay;
 pub mod drone;
+pub mod drop_model;
+pub mod energy;
+pub mod fault;
+pub mod hook;
+pub mod link;
+pub mod rate_limit;
+pub mod stats;
 
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,13 @@
+use std::time::Duration;
+
+/// Quality of the link from a drone to one of its neighbours
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LinkQuality {
+    /// Drop rate for the fragments sent on the link, used instead of the drone's `DropModel`
+    pub pdr: Option<f32>,
+    /// Time every packet spends on the link
+    pub latency: Duration,
+    /// Bits per second the link can carry, fragments wait for their payload to be transmitted.
+    /// `None` means unlimited
+    pub bandwidth: Option<u64>,
+}
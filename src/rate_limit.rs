@@ -0,0 +1,65 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use wg_2024::packet::Packet;
+
+/// Caps the packets a drone handles per second with a token bucket.
+/// Packets exceeding the rate wait for a token, and are dropped once the queue is full
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    /// Packets handled per second on average
+    pub packets_per_second: f64,
+    /// Packets that can be handled right away after an idle period, at least 1
+    pub burst: u32,
+    /// Packets waiting for a token, the ones above are dropped
+    pub queue_capacity: usize,
+}
+
+/// State of the token bucket of a drone
+pub(crate) struct RateLimiter {
+    pub(crate) limit: RateLimit,
+    pub(crate) queue: VecDeque<Packet>,
+    tokens: f64,
+    refilled_at: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        Self {
+            tokens: limit.burst.max(1) as f64,
+            limit,
+            queue: VecDeque::new(),
+            refilled_at: None,
+        }
+    }
+
+    /// Takes a token if one is available
+    pub(crate) fn try_take(&mut self, now: Instant) -> bool {
+        // the bucket starts full, whenever the drone handles its first packet
+        let elapsed = self.refilled_at.map_or(Duration::ZERO, |refilled_at| {
+            now.saturating_duration_since(refilled_at)
+        });
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.limit.packets_per_second)
+            // a bucket holding less than a token would never let anything through
+            .min(self.limit.burst.max(1) as f64);
+        self.refilled_at = Some(now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Returns when the next queued packet gets its token, if any is queued
+    pub(crate) fn next_token_at(&self) -> Option<Instant> {
+        if self.queue.is_empty() || self.limit.packets_per_second <= 0.0 {
+            return None;
+        }
+        let missing = (1.0 - self.tokens).max(0.0);
+        self.refilled_at.map(|refilled_at| {
+            refilled_at + Duration::from_secs_f64(missing / self.limit.packets_per_second)
+        })
+    }
+}
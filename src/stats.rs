@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+
+/// Sessions a drone keeps counters for, the oldest one is forgotten to make room for a new one
+pub const MAX_TRACKED_SESSIONS: usize = 256;
+
+/// Counters kept by a `RustDrone` since it started or since `RustDroneCommand::ResetStats`,
+/// reported with `RustDroneEvent::Stats` when asked with `RustDroneCommand::ReportStats`
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DroneStats {
+    /// Fragments sent to the next hop
+    pub fragments_forwarded: u64,
+    /// Fragments dropped by the drop model or the PDR of a link
+    pub fragments_dropped: u64,
+    /// NACKs sent with `ErrorInRouting`
+    pub nacks_error_in_routing: u64,
+    /// NACKs sent with `DestinationIsDrone`
+    pub nacks_destination_is_drone: u64,
+    /// NACKs sent with `Dropped`
+    pub nacks_dropped: u64,
+    /// NACKs sent with `UnexpectedRecipient`
+    pub nacks_unexpected_recipient: u64,
+    /// Packets whose routing header had no current hop
+    pub malformed_packets: u64,
+    /// Packets dropped because the queue of the next hop was full, unlike random drops
+    pub queue_overflows: u64,
+    /// Packets dropped because the drone was over its rate limit and its queue was full
+    pub rate_limited: u64,
+    /// Flood requests seen, including the repeated ones
+    pub floods_handled: u64,
+    /// Counters of the last `MAX_TRACKED_SESSIONS` sessions, by session id
+    pub sessions: HashMap<u64, SessionStats>,
+}
+
+/// Counters about the fragments of a single session
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SessionStats {
+    /// Fragments of the session the drone tried to forward
+    pub fragments_seen: u64,
+    /// Fragments of the session dropped by the drop model or the PDR of a link
+    pub fragments_dropped: u64,
+    /// Index of the last fragment of the session the drone tried to forward
+    pub last_fragment_index: u64,
+}
@@ -1,14 +1,36 @@
+use super::super::builder::RustDroneBuilder;
+use super::super::byzantine::{ByzantineAction, ByzantineBehaviour, SelectiveForwarding};
+use super::super::clock::{Clock, VirtualClock};
+use super::super::controller::{
+    CrashMode, DroneSnapshot, DroneStatus, EventEnvelope, Heartbeat, HeartbeatWatchdog,
+    MalformedPacketPolicy, RustDroneCommand, RustDroneEvent,
+};
+use super::super::delay::ProcessingDelay;
 use super::super::drone::*;
+use super::super::drop_model::{ControlPdr, DropModel, EveryNth, GilbertElliott, SizeDependent};
+use super::super::energy::EnergyBudget;
+use super::super::fault::{Fault, FaultInjection};
+use super::super::hook::{HookVerdict, PacketHook};
+use super::super::link::LinkQuality;
+use super::super::rate_limit::{RateLimit, RateLimiter};
+use super::super::stats::{DroneStats, SessionStats, MAX_TRACKED_SESSIONS};
 use super::utils::{
     generate_random_config, generate_random_payload, parse_network_from_flood_responses,
-    provision_drones_from_config, send_command_to_drone, send_packet_to_drone, terminate_env,
+    provision_customized_drones_from_config, provision_drones_from_config, recv_event_from_drone,
+    request_snapshot, request_stats, send_command_to_drone, send_packet_to_drone,
+    send_rust_command_to_drone, terminate_env,
 };
-use super::MAX_PACKET_WAIT_TIMEOUT;
+use super::{DRONE_CRASH_TIMEOUT, MAX_PACKET_WAIT_TIMEOUT};
 
-use crossbeam::channel::unbounded;
+use crossbeam::channel::{bounded, unbounded};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
 
 use wg_2024::controller::{DroneCommand, DroneEvent};
+use wg_2024::drone::Drone;
 use wg_2024::network::{NodeId, SourceRoutingHeader};
 use wg_2024::packet::{
     Ack, FloodRequest, FloodResponse, Fragment, Nack, NackType, NodeType, Packet, PacketType,
@@ -46,162 +68,250 @@ fn drone_crashes_upon_cmd() {
 }
 
 #[test]
-fn drone_adds_sender() {
+fn drone_emits_lifecycle_events_on_crash() {
+    let d_id = 11;
     let mut config = HashMap::new();
-    config.insert(11, (0.0, vec![]));
+    config.insert(d_id, (0.0, vec![]));
 
     let (_, env) = provision_drones_from_config(&config);
 
-    send_command_to_drone(&env, 11, DroneCommand::AddSender(12, unbounded().0));
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
+
+    let event_recv = env.get(&d_id).unwrap().event_recv();
 
     terminate_env(env, config);
+
+    assert_eq!(
+        event_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        RustDroneEvent::Crashing(d_id)
+    );
+    assert_eq!(
+        event_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        RustDroneEvent::Crashed(d_id)
+    );
 }
 
 #[test]
-fn drone_removes_sender() {
+fn crashing_drone_still_handles_sender_commands() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let t_id = 201;
     let mut config = HashMap::new();
-    config.insert(0, (0.0, vec![1]));
-    config.insert(1, (0.0, vec![0]));
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, s_recv) = unbounded();
+    let (t_send, t_recv) = unbounded();
 
-    let (_, env) = provision_drones_from_config(&config);
+    let (controller_recv, env) = provision_drones_from_config(&config);
 
-    send_command_to_drone(&env, 0, DroneCommand::RemoveSender(1));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+    send_command_to_drone(&env, d_id, DroneCommand::Crash);
+
+    let node = env.get(&d_id).unwrap();
+    assert_eq!(node.recv_event(), RustDroneEvent::Started(d_id));
+    assert_eq!(node.recv_event(), RustDroneEvent::Crashing(d_id));
+
+    // neighbours come and go while the drone drains the packets in flight
+    send_command_to_drone(&env, d_id, DroneCommand::RemoveSender(s_id));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(t_id, t_send));
+
+    let ack_along = |hops: Vec<NodeId>| Packet {
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        routing_header: SourceRoutingHeader { hops, hop_index: 1 },
+        session_id: 0,
+    };
+    send_packet_to_drone(&env, d_id, ack_along(vec![c_id, d_id, s_id]));
+    send_packet_to_drone(&env, d_id, ack_along(vec![c_id, d_id, t_id]));
+
+    let mut expected = ack_along(vec![c_id, d_id, t_id]);
+    expected.routing_header.hop_index = 2;
+    assert_eq!(
+        t_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected
+    );
+    assert_eq!(
+        controller_recv
+            .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+            .unwrap(),
+        DroneEvent::ControllerShortcut(ack_along(vec![c_id, d_id, s_id]))
+    );
+    assert!(s_recv.try_recv().is_err());
 
     terminate_env(env, config);
 }
 
 #[test]
-fn drone_doesnt_panic_if_removing_non_existent_sender() {
+fn drone_crashes_immediately_without_draining() {
+    let d_id = 11;
     let mut config = HashMap::new();
-    config.insert(0, (0.0, vec![]));
+    config.insert(d_id, (0.0, vec![]));
 
-    let (_, env) = provision_drones_from_config(&config);
+    let (_, mut env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_crash_mode(CrashMode::Immediate)
+    });
 
-    send_command_to_drone(&env, 0, DroneCommand::RemoveSender(1));
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
 
-    terminate_env(env, config);
+    // keep the packet channel open, a graceful crash would wait for it to be closed
+    let packet_send = env.get(&d_id).unwrap().packet_send();
+    send_command_to_drone(&env, d_id, DroneCommand::Crash);
+
+    let node = env.remove(&d_id).unwrap();
+    let event_recv = node.event_recv();
+    node.join(DRONE_CRASH_TIMEOUT).unwrap();
+
+    assert_eq!(
+        event_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        RustDroneEvent::Crashing(d_id)
+    );
+    assert_eq!(
+        event_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        RustDroneEvent::Crashed(d_id)
+    );
+    assert!(packet_send
+        .send(Packet {
+            pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![d_id],
+                hop_index: 0,
+            },
+            session_id: 0,
+        })
+        .is_err());
 }
 
 #[test]
-fn drone_updates_pdr() {
+fn drone_stops_draining_after_timeout() {
+    let d_id = 11;
     let c_id = 100;
     let s_id = 200;
-    let d_id = 0;
+    let drain_timeout = Duration::from_millis(50);
     let mut config = HashMap::new();
+    let (s_send, s_recv) = unbounded();
     config.insert(d_id, (0.0, vec![]));
-    let (c_send, c_recv) = unbounded();
-    let (s_send, _s_recv) = unbounded();
 
-    let (_, env) = provision_drones_from_config(&config);
+    let (_, mut env) = provision_customized_drones_from_config(&config, move |_, drone| {
+        drone.with_crash_mode(CrashMode::DrainWithTimeout(drain_timeout))
+    });
 
-    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
-    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send.clone()));
-    send_command_to_drone(&env, d_id, DroneCommand::SetPacketDropRate(1.0));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
 
-    let (payload_len, payload) = generate_random_payload();
+    // keep the packet channel open, the drone has to give up on its own
+    let packet_send = env.get(&d_id).unwrap().packet_send();
+    let start_time = Instant::now();
+    send_command_to_drone(&env, d_id, DroneCommand::Crash);
 
+    // packets in flight are still forwarded while draining
     let msg = Packet {
-        pack_type: PacketType::MsgFragment(Fragment {
-            fragment_index: 0,
-            total_n_fragments: 1,
-            length: payload_len,
-            data: payload,
-        }),
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
         routing_header: SourceRoutingHeader {
             hops: vec![c_id, d_id, s_id],
             hop_index: 1,
         },
-        session_id: 1,
-    };
-
-    send_packet_to_drone(&env, d_id, msg);
-
-    let expected_packet = Packet {
-        pack_type: PacketType::Nack(Nack {
-            fragment_index: 0,
-            nack_type: NackType::Dropped,
-        }),
-        routing_header: SourceRoutingHeader {
-            hops: vec![d_id, c_id],
-            hop_index: 1,
-        },
-        session_id: 1,
+        session_id: rand::random::<u64>(),
     };
+    packet_send.send(msg.clone()).unwrap();
 
+    let mut expected = msg;
+    expected.routing_header.hop_index = 2;
     assert_eq!(
-        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
-        expected_packet
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected
     );
 
-    terminate_env(env, config);
+    let node = env.remove(&d_id).unwrap();
+    node.join(DRONE_CRASH_TIMEOUT).unwrap();
+    assert!(start_time.elapsed() >= drain_timeout);
 }
 
 #[test]
-fn drone_returns_nack_if_destination_is_drone() {
-    let d_id = 0;
+fn unresponsive_drone_stops_processing_packets() {
+    let d_id = 11;
     let c_id = 100;
+    let s_id = 200;
     let mut config = HashMap::new();
+    let (s_send, s_recv) = unbounded();
     config.insert(d_id, (0.0, vec![]));
-    let (c_send, c_recv) = unbounded();
 
-    let (_, env) = provision_drones_from_config(&config);
+    let (_, mut env) = provision_drones_from_config(&config);
 
-    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
 
-    let session_id = rand::random::<u64>();
-    let (payload_len, payload) = generate_random_payload();
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+    send_rust_command_to_drone(&env, d_id, RustDroneCommand::Crash(CrashMode::Unresponsive));
 
-    let sending_packet = Packet {
-        pack_type: PacketType::MsgFragment(Fragment {
-            fragment_index: 0,
-            total_n_fragments: 1,
-            length: payload_len,
-            data: payload,
-        }),
-        routing_header: SourceRoutingHeader {
-            hops: vec![c_id, d_id],
-            hop_index: 1,
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Crashing(d_id)
+    );
+
+    let (payload_len, payload) = generate_random_payload();
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::MsgFragment(Fragment {
+                fragment_index: 0,
+                total_n_fragments: 1,
+                length: payload_len,
+                data: payload,
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![c_id, d_id, s_id],
+                hop_index: 1,
+            },
+            session_id: rand::random(),
         },
-        session_id,
-    };
+    );
 
-    send_packet_to_drone(&env, d_id, sending_packet.clone());
+    assert!(s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).is_err());
+    assert!(env.get(&d_id).unwrap().is_alive());
 
-    let expected_packet = Packet {
-        pack_type: PacketType::Nack(Nack {
-            fragment_index: 0,
-            nack_type: NackType::DestinationIsDrone,
-        }),
-        routing_header: SourceRoutingHeader {
-            hops: vec![d_id, c_id],
-            hop_index: 1,
-        },
-        session_id,
-    };
+    // the drone only goes away together with its controller
+    let node = env.remove(&d_id).unwrap();
+    let event_recv = node.event_recv();
+    node.join(DRONE_CRASH_TIMEOUT).unwrap();
 
     assert_eq!(
-        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
-        expected_packet
+        event_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        RustDroneEvent::Crashed(d_id)
     );
-
-    terminate_env(env, config);
 }
 
 #[test]
-fn drone_returns_nack_when_error_in_rouing() {
+fn byzantine_drone_corrupts_and_duplicates_fragments() {
     let d_id = 0;
     let c_id = 100;
+    let s_id = 200;
     let mut config = HashMap::new();
-    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
     config.insert(d_id, (0.0, vec![]));
 
-    let (_, env) = provision_drones_from_config(&config);
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_byzantine_behaviour(ByzantineBehaviour {
+            corrupt_payload: 1.0,
+            duplicate: 1.0,
+            ..Default::default()
+        })
+    });
 
-    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
 
-    let session_id = rand::random::<u64>();
-    let (payload_len, payload) = generate_random_payload();
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
 
+    let (payload_len, payload) = generate_random_payload();
     let sending_packet = Packet {
         pack_type: PacketType::MsgFragment(Fragment {
             fragment_index: 0,
@@ -210,121 +320,2835 @@ fn drone_returns_nack_when_error_in_rouing() {
             data: payload,
         }),
         routing_header: SourceRoutingHeader {
-            hops: vec![c_id, d_id, 1],
+            hops: vec![c_id, d_id, s_id],
             hop_index: 1,
         },
-        session_id,
+        session_id: rand::random(),
     };
 
     send_packet_to_drone(&env, d_id, sending_packet.clone());
 
-    let expected_packet = Packet {
-        pack_type: PacketType::Nack(Nack {
-            fragment_index: 0,
-            nack_type: NackType::ErrorInRouting(1),
-        }),
-        routing_header: SourceRoutingHeader {
-            hops: vec![d_id, c_id],
-            hop_index: 1,
-        },
-        session_id,
-    };
+    let forwarded = s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+    assert_eq!(
+        forwarded,
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap()
+    );
+    assert_eq!(forwarded.routing_header.hop_index, 2);
+    match &forwarded.pack_type {
+        PacketType::MsgFragment(fragment) => {
+            let length = payload_len as usize;
+            assert_ne!(fragment.data[..length], payload[..length]);
+            assert_eq!(fragment.data[length..], payload[length..]);
+        }
+        _ => panic!("Received packet was not a MsgFragment"),
+    }
 
     assert_eq!(
-        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
-        expected_packet
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Byzantine(d_id, ByzantineAction::CorruptedPayload, forwarded.clone())
+    );
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Byzantine(d_id, ByzantineAction::Duplicated, forwarded)
     );
 
     terminate_env(env, config);
 }
 
 #[test]
-fn drone_returns_nack_if_unexpected_recipient() {
-    let d_id = 0;
+fn byzantine_drone_forges_flood_path_traces() {
+    let d_id = 1;
     let c_id = 100;
+    let s_id = 200;
     let mut config = HashMap::new();
     let (c_send, c_recv) = unbounded();
     config.insert(d_id, (0.0, vec![]));
 
-    let (_, env) = provision_drones_from_config(&config);
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_byzantine_behaviour(ByzantineBehaviour {
+            forge_path_trace: 1.0,
+            ..Default::default()
+        })
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+
+    let path_trace = vec![
+        (c_id, NodeType::Client),
+        (d_id, NodeType::Drone),
+        (s_id, NodeType::Server),
+    ];
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::FloodResponse(FloodResponse {
+                flood_id: 0,
+                path_trace: path_trace.clone(),
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![s_id, d_id, c_id],
+                hop_index: 1,
+            },
+            session_id: rand::random(),
+        },
+    );
 
-    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    let forwarded = c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+    let forged_trace = match &forwarded.pack_type {
+        PacketType::FloodResponse(flood_response) => flood_response.path_trace.clone(),
+        _ => panic!("Received packet was not a FloodResponse"),
+    };
 
-    let session_id = rand::random::<u64>();
-    let (payload_len, payload) = generate_random_payload();
+    // the fake node is claimed to be linked right after the drone
+    assert_eq!(forged_trace.len(), path_trace.len() + 1);
+    assert_eq!(forged_trace[..2], path_trace[..2]);
+    assert_eq!(forged_trace[3..], path_trace[2..]);
+    assert!(!path_trace.iter().any(|(id, _)| *id == forged_trace[2].0));
 
-    let sending_packet = Packet {
+    let event_recv = env.get(&d_id).unwrap().event_recv();
+    assert!(event_recv.try_iter().any(|e| e
+        == RustDroneEvent::Byzantine(d_id, ByzantineAction::ForgedPathTrace, forwarded.clone())));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_crashes_when_energy_runs_out() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, s_recv) = unbounded();
+
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_energy_budget(EnergyBudget {
+            energy: 3,
+            forward_cost: 1,
+            flood_cost: 1,
+        })
+    });
+
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    for session_id in 0..3 {
+        send_packet_to_drone(
+            &env,
+            d_id,
+            Packet {
+                pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+                routing_header: SourceRoutingHeader {
+                    hops: vec![c_id, d_id, s_id],
+                    hop_index: 1,
+                },
+                session_id,
+            },
+        );
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+    }
+
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::EnergyDepleted(d_id)
+    );
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Crashing(d_id)
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_out_of_energy_stops_forwarding_mid_burst() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let (controller_send, controller_recv) = unbounded();
+    let (_command_send, command_recv) = unbounded();
+    let (packet_send, packet_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment = |fragment_index| Packet {
         pack_type: PacketType::MsgFragment(Fragment {
-            fragment_index: 0,
-            total_n_fragments: 1,
+            fragment_index,
+            total_n_fragments: 10,
             length: payload_len,
             data: payload,
         }),
         routing_header: SourceRoutingHeader {
-            hops: vec![c_id, 99, 1],
+            hops: vec![c_id, d_id, s_id],
             hop_index: 1,
         },
-        session_id,
+        session_id: 0,
     };
 
-    send_packet_to_drone(&env, d_id, sending_packet.clone());
+    // the whole burst is queued before the drone starts, with energy for a single fragment
+    for fragment_index in 0..10 {
+        packet_send.send(fragment(fragment_index)).unwrap();
+    }
+    drop(packet_send);
+
+    let d_t = thread::spawn(move || {
+        RustDrone::new(
+            d_id,
+            controller_send,
+            command_recv,
+            packet_recv,
+            HashMap::from([(s_id, s_send)]),
+            0.0,
+        )
+        .with_energy_budget(EnergyBudget {
+            energy: 1,
+            forward_cost: 1,
+            flood_cost: 1,
+        })
+        .with_crash_mode(CrashMode::Immediate)
+        .run();
+    });
+
+    d_t.join().expect("Drone thread panicked");
+
+    assert_eq!(s_recv.iter().count(), 1);
+    // the drone can't send the NACKs either, so they reach the clients through the controller
+    let nacks = controller_recv
+        .try_iter()
+        .filter(|e| {
+            matches!(e, DroneEvent::ControllerShortcut(Packet {
+                pack_type: PacketType::Nack(Nack {
+                    nack_type: NackType::ErrorInRouting(id),
+                    ..
+                }),
+                ..
+            }) if *id == d_id)
+        })
+        .count();
+    assert_eq!(nacks, 9);
+}
 
-    let expected_packet = Packet {
-        pack_type: PacketType::Nack(Nack {
-            fragment_index: 0,
-            nack_type: NackType::UnexpectedRecipient(d_id),
-        }),
-        routing_header: SourceRoutingHeader {
-            hops: vec![d_id, c_id],
-            hop_index: 1,
+#[test]
+fn byzantine_drone_corrupts_hop_index_and_sends_bogus_nacks() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let t_id = 201;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_byzantine_behaviour(ByzantineBehaviour {
+            corrupt_hop_index: 1.0,
+            bogus_nack: 1.0,
+            ..Default::default()
+        })
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let session_id = rand::random::<u64>();
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::MsgFragment(Fragment {
+                fragment_index: 0,
+                total_n_fragments: 1,
+                length: payload_len,
+                data: payload,
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![c_id, d_id, s_id, t_id],
+                hop_index: 1,
+            },
+            session_id,
         },
-        session_id,
-    };
+    );
 
+    // the fragment still reaches the next hop, but pointing to the wrong one
+    let forwarded = s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+    assert_ne!(forwarded.routing_header.hop_index, 2);
+
+    let nack = c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
     assert_eq!(
-        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        nack.pack_type,
+        PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::Dropped,
+        })
+    );
+    assert_eq!(nack.routing_header.hops, vec![d_id, c_id]);
+    assert_eq!(nack.session_id, session_id);
+
+    let event_recv = env.get(&d_id).unwrap().event_recv();
+    let actions = event_recv
+        .try_iter()
+        .filter_map(|e| match e {
+            RustDroneEvent::Byzantine(_, action, _) => Some(action),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    // the drone tampers with its own NACK as well
+    assert_eq!(
+        actions,
+        vec![
+            ByzantineAction::CorruptedHopIndex,
+            ByzantineAction::BogusNack,
+            ByzantineAction::CorruptedHopIndex,
+        ]
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn byzantine_drone_reorders_flood_path_traces() {
+    let d_id = 1;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_byzantine_behaviour(ByzantineBehaviour {
+            reorder_path_trace: 1.0,
+            ..Default::default()
+        })
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+
+    let mut path_trace = vec![
+        (c_id, NodeType::Client),
+        (d_id, NodeType::Drone),
+        (s_id, NodeType::Server),
+    ];
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::FloodResponse(FloodResponse {
+                flood_id: 0,
+                path_trace: path_trace.clone(),
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![s_id, d_id, c_id],
+                hop_index: 1,
+            },
+            session_id: rand::random(),
+        },
+    );
+
+    let forwarded = c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+    let mut reordered_trace = match forwarded.pack_type {
+        PacketType::FloodResponse(flood_response) => flood_response.path_trace,
+        _ => panic!("Received packet was not a FloodResponse"),
+    };
+
+    // same nodes, different order
+    assert_ne!(reordered_trace, path_trace);
+    reordered_trace.sort_by_key(|(id, _)| *id);
+    path_trace.sort_by_key(|(id, _)| *id);
+    assert_eq!(reordered_trace, path_trace);
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn selective_forwarding_drone_silently_drops_targeted_fragments() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let t_id = 201;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_customized_drones_from_config(&config, move |_, drone| {
+        drone.with_selective_forwarding(SelectiveForwarding {
+            targets: HashSet::from([t_id]),
+            drop_rate: 1.0,
+        })
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment_along = |hops: Vec<NodeId>| Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader { hops, hop_index: 1 },
+        session_id: 0,
+    };
+
+    // the fragment for the target is lost even though its next hop is reachable
+    send_packet_to_drone(&env, d_id, fragment_along(vec![c_id, d_id, s_id, t_id]));
+    send_packet_to_drone(&env, d_id, fragment_along(vec![c_id, d_id, s_id]));
+
+    let mut expected = fragment_along(vec![c_id, d_id, s_id]);
+    expected.routing_header.hop_index = 2;
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected
+    );
+    assert!(s_recv.try_recv().is_err());
+    assert!(c_recv.try_recv().is_err());
+
+    let event_recv = env.get(&d_id).unwrap().event_recv();
+    assert!(event_recv.try_iter().any(|e| e
+        == RustDroneEvent::Byzantine(
+            d_id,
+            ByzantineAction::SilentlyDropped,
+            fragment_along(vec![c_id, d_id, s_id, t_id])
+        )));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_injects_duplicated_fragments() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_fault_injection(FaultInjection {
+            duplicate: 1.0,
+            ..Default::default()
+        })
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let packet = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    send_packet_to_drone(&env, d_id, packet.clone());
+
+    let mut expected = packet.clone();
+    expected.routing_header.hop_index = 2;
+    for _ in 0..2 {
+        assert_eq!(
+            s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+            expected
+        );
+    }
+    assert!(s_recv.try_recv().is_err());
+
+    let event_recv = env.get(&d_id).unwrap().event_recv();
+    assert!(event_recv
+        .try_iter()
+        .any(|e| e == RustDroneEvent::FaultInjected(d_id, Fault::Duplicated, expected.clone())));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_injects_reordered_fragments() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_fault_injection(FaultInjection {
+            reorder: 1.0,
+            ..Default::default()
+        })
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    for fragment_index in 0..3 {
+        send_packet_to_drone(
+            &env,
+            d_id,
+            Packet {
+                pack_type: PacketType::MsgFragment(Fragment {
+                    fragment_index,
+                    total_n_fragments: 3,
+                    length: payload_len,
+                    data: payload,
+                }),
+                routing_header: SourceRoutingHeader {
+                    hops: vec![c_id, d_id, s_id],
+                    hop_index: 1,
+                },
+                session_id: 0,
+            },
+        );
+    }
+
+    // every other fragment is held back and overtaken, the last one is released on its own
+    let received = (0..3)
+        .map(|_| {
+            match s_recv
+                .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+                .unwrap()
+                .pack_type
+            {
+                PacketType::MsgFragment(fragment) => fragment.fragment_index,
+                other => panic!("Unexpected packet type: {:?}", other),
+            }
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(received, vec![1, 0, 2]);
+
+    let event_recv = env.get(&d_id).unwrap().event_recv();
+    assert_eq!(
+        event_recv
+            .try_iter()
+            .filter(|e| matches!(e, RustDroneEvent::FaultInjected(_, Fault::Reordered, _)))
+            .count(),
+        2
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_suppresses_duplicate_fragments() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_duplicate_suppression(16)
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment = |fragment_index| Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index,
+            total_n_fragments: 2,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    send_packet_to_drone(&env, d_id, fragment(0));
+    send_packet_to_drone(&env, d_id, fragment(0));
+    send_packet_to_drone(&env, d_id, fragment(1));
+
+    let received = (0..2)
+        .map(|_| {
+            match s_recv
+                .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+                .unwrap()
+                .pack_type
+            {
+                PacketType::MsgFragment(fragment) => fragment.fragment_index,
+                other => panic!("Unexpected packet type: {:?}", other),
+            }
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(received, vec![0, 1]);
+    assert!(s_recv.try_recv().is_err());
+
+    let event_recv = env.get(&d_id).unwrap().event_recv();
+    assert!(event_recv
+        .try_iter()
+        .any(|e| e == RustDroneEvent::DuplicateSuppressed(d_id, fragment(0))));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_forwards_fragments_sent_again_after_a_nack() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (1.0, vec![]));
+
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_duplicate_suppression(16)
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    let nack = |hops| Packet {
+        pack_type: PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::Dropped,
+        }),
+        routing_header: SourceRoutingHeader { hops, hop_index: 1 },
+        session_id: 0,
+    };
+    let mut forwarded = fragment.clone();
+    forwarded.routing_header.hop_index = 2;
+
+    // dropped by the drone itself, then sent again
+    send_packet_to_drone(&env, d_id, fragment.clone());
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        nack(vec![d_id, c_id])
+    );
+    send_command_to_drone(&env, d_id, DroneCommand::SetPacketDropRate(0.0));
+    send_packet_to_drone(&env, d_id, fragment.clone());
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        forwarded
+    );
+
+    // dropped further along the route, then sent again
+    let mut nack_from_server = nack(vec![s_id, d_id, c_id]);
+    send_packet_to_drone(&env, d_id, nack_from_server.clone());
+    nack_from_server.routing_header.hop_index = 2;
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        nack_from_server
+    );
+    send_packet_to_drone(&env, d_id, fragment);
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        forwarded
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_emits_stopped_event_when_packet_channel_closes() {
+    let d_id = 11;
+    let (controller_send, _controller_recv) = unbounded();
+    let (_command_send, command_recv) = unbounded();
+    let (packet_send, packet_recv) = unbounded();
+    let (event_send, event_recv) = unbounded();
+
+    let d_t = thread::spawn(move || {
+        RustDrone::new(
+            d_id,
+            controller_send,
+            command_recv,
+            packet_recv,
+            HashMap::new(),
+            0.0,
+        )
+        .with_event_send(event_send)
+        .run();
+    });
+
+    assert_eq!(
+        event_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        RustDroneEvent::Started(d_id)
+    );
+
+    drop(packet_send);
+
+    assert_eq!(
+        event_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        RustDroneEvent::Stopped(d_id)
+    );
+    d_t.join().expect("Drone thread panicked");
+}
+
+#[test]
+fn builder_creates_drone_with_options() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let (controller_send, _controller_recv) = unbounded();
+    let (_command_send, command_recv) = unbounded();
+    let (packet_send, packet_recv) = unbounded();
+    let (event_send, event_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+
+    let mut drone = RustDroneBuilder::new(d_id, controller_send, command_recv, packet_recv)
+        .neighbour(s_id, s_send)
+        .pdr(0.0)
+        .seed(42)
+        .log_target("custom-drone")
+        .event_send(event_send)
+        .fault_injection(FaultInjection {
+            duplicate: 1.0,
+            ..Default::default()
+        })
+        .customize(|drone| drone.with_max_hops(3))
+        .build();
+    let d_t = thread::spawn(move || drone.run());
+
+    assert_eq!(
+        event_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        RustDroneEvent::Started(d_id)
+    );
+
+    let (payload_len, payload) = generate_random_payload();
+    let mut msg = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    packet_send.send(msg.clone()).unwrap();
+
+    msg.routing_header.hop_index = 2;
+    for _ in 0..2 {
+        assert_eq!(s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(), msg);
+    }
+
+    drop(packet_send);
+    d_t.join().expect("Drone thread panicked");
+}
+
+#[test]
+fn drone_sends_timestamped_event_envelopes() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, _s_recv) = unbounded();
+    let (envelope_send, envelope_recv) = unbounded::<EventEnvelope>();
+
+    let (controller_recv, env) =
+        provision_customized_drones_from_config(&config, move |_, drone| {
+            drone.with_event_envelope_send(envelope_send.clone())
+        });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let mut msg = Packet {
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: rand::random::<u64>(),
+    };
+
+    let start_time = Instant::now();
+    send_packet_to_drone(&env, d_id, msg.clone());
+    msg.routing_header.hop_index = 2;
+
+    let envelope = envelope_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+    assert_eq!(envelope.origin, d_id);
+    assert!(envelope.timestamp >= start_time && envelope.timestamp <= Instant::now());
+    assert!(matches!(envelope.event, DroneEvent::PacketSent(p) if p == msg));
+
+    // the controller still receives the plain event
+    assert!(matches!(
+        controller_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        DroneEvent::PacketSent(p) if p == msg
+    ));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_handles_control_packets_before_queued_fragments() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let (controller_send, _controller_recv) = unbounded();
+    let (_command_send, command_recv) = unbounded();
+    let (packet_send, packet_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    let ack = Packet {
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 1,
+    };
+
+    // the ack is queued behind a burst of fragments before the drone starts
+    for _ in 0..10 {
+        packet_send.send(fragment.clone()).unwrap();
+    }
+    packet_send.send(ack).unwrap();
+    drop(packet_send);
+
+    let d_t = thread::spawn(move || {
+        RustDrone::new(
+            d_id,
+            controller_send,
+            command_recv,
+            packet_recv,
+            HashMap::from([(s_id, s_send)]),
+            0.0,
+        )
+        .run();
+    });
+
+    let forwarded = s_recv.iter().collect::<Vec<_>>();
+    assert_eq!(forwarded.len(), 11);
+    assert!(matches!(forwarded[0].pack_type, PacketType::Ack(_)));
+    assert!(forwarded[1..]
+        .iter()
+        .all(|p| matches!(p.pack_type, PacketType::MsgFragment(_))));
+
+    d_t.join().expect("Drone thread panicked");
+}
+
+#[test]
+fn drone_reports_stats() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let o_id = 201;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (n_send, n_recv) = unbounded();
+    // nobody ever takes from this queue
+    let (o_send, _o_recv) = bounded(0);
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, n_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, n_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(o_id, o_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment_along = |hops: Vec<NodeId>| Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader { hops, hop_index: 1 },
+        session_id: 0,
+    };
+
+    // forwarded, refused because of an unknown next hop and a wrong recipient,
+    // dropped on a full queue and missing its current hop
+    send_packet_to_drone(&env, d_id, fragment_along(vec![c_id, d_id, s_id]));
+    send_packet_to_drone(&env, d_id, fragment_along(vec![c_id, d_id, 1]));
+    send_packet_to_drone(&env, d_id, fragment_along(vec![c_id, 1, s_id]));
+    send_packet_to_drone(&env, d_id, fragment_along(vec![c_id, d_id, o_id]));
+    send_packet_to_drone(&env, d_id, fragment_along(vec![]));
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::FloodRequest(FloodRequest {
+                flood_id: 0,
+                initiator_id: c_id,
+                path_trace: vec![(c_id, NodeType::Client)],
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: Vec::new(),
+                hop_index: 0,
+            },
+            session_id: 0,
+        },
+    );
+    for _ in 0..5 {
+        n_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+    }
+
+    send_command_to_drone(&env, d_id, DroneCommand::SetPacketDropRate(1.0));
+    send_packet_to_drone(&env, d_id, fragment_along(vec![c_id, d_id, s_id]));
+    n_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+
+    assert_eq!(
+        request_stats(&env, d_id),
+        DroneStats {
+            fragments_forwarded: 1,
+            fragments_dropped: 1,
+            nacks_error_in_routing: 1,
+            nacks_destination_is_drone: 0,
+            nacks_dropped: 2,
+            nacks_unexpected_recipient: 1,
+            floods_handled: 1,
+            malformed_packets: 1,
+            // the fragment and the flood request both hit the full queue
+            queue_overflows: 2,
+            rate_limited: 0,
+            sessions: HashMap::from([(
+                0,
+                SessionStats {
+                    fragments_seen: 3,
+                    fragments_dropped: 1,
+                    last_fragment_index: 0,
+                }
+            )]),
+        }
+    );
+
+    send_rust_command_to_drone(&env, d_id, RustDroneCommand::ResetStats);
+    assert_eq!(request_stats(&env, d_id), DroneStats::default());
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_forgets_oldest_sessions() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, s_recv) = unbounded();
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    for session_id in 0..=MAX_TRACKED_SESSIONS as u64 {
+        send_packet_to_drone(
+            &env,
+            d_id,
+            Packet {
+                pack_type: PacketType::MsgFragment(Fragment {
+                    fragment_index: 0,
+                    total_n_fragments: 1,
+                    length: payload_len,
+                    data: payload,
+                }),
+                routing_header: SourceRoutingHeader {
+                    hops: vec![c_id, d_id, s_id],
+                    hop_index: 1,
+                },
+                session_id,
+            },
+        );
+    }
+    for _ in 0..=MAX_TRACKED_SESSIONS {
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+    }
+
+    let stats = request_stats(&env, d_id);
+    assert_eq!(stats.sessions.len(), MAX_TRACKED_SESSIONS);
+    assert!(!stats.sessions.contains_key(&0));
+    assert!(stats.sessions.contains_key(&(MAX_TRACKED_SESSIONS as u64)));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_confirms_commands_applied_before_sync() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, s_recv) = unbounded();
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+    send_rust_command_to_drone(&env, d_id, RustDroneCommand::Sync(7));
+
+    assert!(
+        std::iter::from_fn(|| Some(recv_event_from_drone(&env, d_id)))
+            .any(|event| event == RustDroneEvent::Synced(d_id, 7))
+    );
+
+    // the sender is known for sure, the fragment can't race with it
+    let (payload_len, payload) = generate_random_payload();
+    let mut fragment = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    send_packet_to_drone(&env, d_id, fragment.clone());
+
+    fragment.routing_header.hop_index = 2;
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        fragment
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_reports_snapshot() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.3, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, unbounded().0));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, unbounded().0));
+    send_command_to_drone(&env, d_id, DroneCommand::SetPacketDropRate(0.5));
+    send_rust_command_to_drone(&env, d_id, RustDroneCommand::Pause);
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![c_id, d_id, s_id],
+                hop_index: 1,
+            },
+            session_id: 0,
+        },
+    );
+    // the packet is counted whether it's still in the channel or already buffered
+    assert_eq!(
+        request_snapshot(&env, d_id),
+        DroneSnapshot {
+            neighbours: vec![c_id, s_id],
+            pdr: 0.5,
+            status: DroneStatus::Paused,
+            queue_length: 1,
+        }
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn paused_drone_buffers_packets_until_resumed() {
+    let d_id = 11;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+
+    let (controller_recv, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_pause_buffer_capacity(2)
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+    send_rust_command_to_drone(&env, d_id, RustDroneCommand::Pause);
+
+    let ack = |session_id: u64| Packet {
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    for session_id in 0..3 {
+        send_packet_to_drone(&env, d_id, ack(session_id));
+    }
+
+    // the buffer only holds two packets, the third one must not be lost
+    assert!(matches!(
+        controller_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        DroneEvent::ControllerShortcut(p) if p == ack(2)
+    ));
+
+    // a fragment that doesn't fit is NACKed like any other dropped fragment
+    let (payload_len, payload) = generate_random_payload();
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::MsgFragment(Fragment {
+                fragment_index: 3,
+                total_n_fragments: 4,
+                length: payload_len,
+                data: payload,
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![c_id, d_id, s_id],
+                hop_index: 1,
+            },
+            session_id: 3,
+        },
+    );
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        Packet {
+            pack_type: PacketType::Nack(Nack {
+                fragment_index: 3,
+                nack_type: NackType::Dropped,
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![d_id, c_id],
+                hop_index: 1,
+            },
+            session_id: 3,
+        }
+    );
+    assert!(s_recv.try_recv().is_err());
+
+    // only the fragment is reported as dropped, the ack is delivered by the controller
+    let dropped = std::iter::from_fn(|| controller_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).ok())
+        .find_map(|event| match event {
+            DroneEvent::PacketDropped(packet) => Some(packet),
+            _ => None,
+        })
+        .unwrap();
+    assert!(matches!(dropped.pack_type, PacketType::MsgFragment(_)));
+
+    send_rust_command_to_drone(&env, d_id, RustDroneCommand::Resume);
+
+    for session_id in 0..2 {
+        let mut expected = ack(session_id);
+        expected.routing_header.hop_index = 2;
+        assert_eq!(
+            s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+            expected
+        );
+    }
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_adds_sender() {
+    let mut config = HashMap::new();
+    config.insert(11, (0.0, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, 11, DroneCommand::AddSender(12, unbounded().0));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_removes_sender() {
+    let mut config = HashMap::new();
+    config.insert(0, (0.0, vec![1]));
+    config.insert(1, (0.0, vec![0]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, 0, DroneCommand::RemoveSender(1));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_doesnt_panic_if_removing_non_existent_sender() {
+    let mut config = HashMap::new();
+    config.insert(0, (0.0, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, 0, DroneCommand::RemoveSender(1));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_reports_neighbours_on_query() {
+    let mut config = HashMap::new();
+    config.insert(0, (0.0, vec![1, 2]));
+    config.insert(1, (0.0, vec![0]));
+    config.insert(2, (0.0, vec![0]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    assert_eq!(recv_event_from_drone(&env, 0), RustDroneEvent::Started(0));
+
+    send_rust_command_to_drone(&env, 0, RustDroneCommand::QueryNeighbours);
+
+    assert_eq!(
+        recv_event_from_drone(&env, 0),
+        RustDroneEvent::Neighbours(0, vec![1, 2])
+    );
+
+    send_command_to_drone(&env, 0, DroneCommand::RemoveSender(2));
+    send_rust_command_to_drone(&env, 0, RustDroneCommand::QueryNeighbours);
+
+    assert_eq!(
+        recv_event_from_drone(&env, 0),
+        RustDroneEvent::Neighbours(0, vec![1])
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_updates_pdr() {
+    let c_id = 100;
+    let s_id = 200;
+    let d_id = 0;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (c_send, c_recv) = unbounded();
+    let (s_send, _s_recv) = unbounded();
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::SetPacketDropRate(1.0));
+
+    let (payload_len, payload) = generate_random_payload();
+
+    let msg = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 1,
+    };
+
+    send_packet_to_drone(&env, d_id, msg);
+
+    let expected_packet = Packet {
+        pack_type: PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::Dropped,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![d_id, c_id],
+            hop_index: 1,
+        },
+        session_id: 1,
+    };
+
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected_packet
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn size_dependent_drop_model_scales_with_fragment_length() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (1.0, vec![]));
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+
+    let (_, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_drop_model(Box::new(SizeDependent::new(1.0)))
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send.clone()));
+
+    let mut msg = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 2,
+            length: 0,
+            data: [0; 128],
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 1,
+    };
+
+    // an empty fragment is never dropped
+    send_packet_to_drone(&env, d_id, msg.clone());
+
+    let mut expected_packet = msg.clone();
+    expected_packet.routing_header.hop_index = 2;
+
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected_packet
+    );
+
+    // a full fragment is dropped with the full PDR
+    msg.pack_type = PacketType::MsgFragment(Fragment {
+        fragment_index: 1,
+        total_n_fragments: 2,
+        length: 128,
+        data: [1; 128],
+    });
+    send_packet_to_drone(&env, d_id, msg);
+
+    let expected_packet = Packet {
+        pack_type: PacketType::Nack(Nack {
+            fragment_index: 1,
+            nack_type: NackType::Dropped,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![d_id, c_id],
+            hop_index: 1,
+        },
+        session_id: 1,
+    };
+
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected_packet
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_returns_nack_if_destination_is_drone() {
+    let d_id = 0;
+    let c_id = 100;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (c_send, c_recv) = unbounded();
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+
+    let session_id = rand::random::<u64>();
+    let (payload_len, payload) = generate_random_payload();
+
+    let sending_packet = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    send_packet_to_drone(&env, d_id, sending_packet.clone());
+
+    let expected_packet = Packet {
+        pack_type: PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::DestinationIsDrone,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![d_id, c_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected_packet
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_returns_nack_when_error_in_rouing() {
+    let d_id = 0;
+    let c_id = 100;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+
+    let session_id = rand::random::<u64>();
+    let (payload_len, payload) = generate_random_payload();
+
+    let sending_packet = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, 1],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    send_packet_to_drone(&env, d_id, sending_packet.clone());
+
+    let expected_packet = Packet {
+        pack_type: PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::ErrorInRouting(1),
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![d_id, c_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected_packet
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_returns_nack_on_cyclic_route() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send.clone()));
+
+    // the route comes back to the drone, then bounces between two nodes
+    for hops in [
+        vec![c_id, d_id, s_id, d_id],
+        vec![c_id, d_id, s_id, 1, s_id],
+    ] {
+        let session_id = rand::random::<u64>();
+        let (payload_len, payload) = generate_random_payload();
+
+        let sending_packet = Packet {
+            pack_type: PacketType::MsgFragment(Fragment {
+                fragment_index: 0,
+                total_n_fragments: 1,
+                length: payload_len,
+                data: payload,
+            }),
+            routing_header: SourceRoutingHeader { hops, hop_index: 1 },
+            session_id,
+        };
+
+        send_packet_to_drone(&env, d_id, sending_packet.clone());
+
+        let expected_packet = Packet {
+            pack_type: PacketType::Nack(Nack {
+                fragment_index: 0,
+                nack_type: NackType::ErrorInRouting(s_id),
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![d_id, c_id],
+                hop_index: 1,
+            },
+            session_id,
+        };
+
+        assert_eq!(
+            c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+            expected_packet
+        );
+        assert_eq!(
+            recv_event_from_drone(&env, d_id),
+            RustDroneEvent::CyclicRoute(d_id, sending_packet)
+        );
+    }
+    assert!(s_recv.try_recv().is_err());
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn flood_response_going_around_a_loop_is_forwarded() {
+    let d_id = 1;
+    let c_id = 100;
+    let n_id = 3;
+    let mut config = HashMap::new();
+    let (n_send, n_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, unbounded().0));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(n_id, n_send));
+
+    let flood_id = 7;
+    let flood_request = |path_trace| Packet {
+        pack_type: PacketType::FloodRequest(FloodRequest {
+            flood_id,
+            initiator_id: c_id,
+            path_trace,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: Vec::new(),
+            hop_index: 0,
+        },
+        session_id: 0,
+    };
+
+    send_packet_to_drone(&env, d_id, flood_request(vec![(c_id, NodeType::Client)]));
+    assert!(matches!(
+        n_recv
+            .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+            .unwrap()
+            .pack_type,
+        PacketType::FloodRequest(_)
+    ));
+
+    // the flood also went 1 -> 2 -> 3 and came back, the response retraces the loop
+    let path_trace = vec![
+        (c_id, NodeType::Client),
+        (d_id, NodeType::Drone),
+        (2, NodeType::Drone),
+        (n_id, NodeType::Drone),
+    ];
+    send_packet_to_drone(&env, d_id, flood_request(path_trace.clone()));
+
+    let mut path_trace = path_trace;
+    path_trace.push((d_id, NodeType::Drone));
+    assert_eq!(
+        n_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        Packet {
+            pack_type: PacketType::FloodResponse(FloodResponse {
+                flood_id,
+                path_trace,
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![d_id, n_id, 2, d_id, c_id],
+                hop_index: 1,
+            },
+            session_id: 0,
+        }
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_returns_nack_on_too_long_route() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) =
+        provision_customized_drones_from_config(&config, |_, drone| drone.with_max_hops(4));
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment_along = |hops: Vec<NodeId>| Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader { hops, hop_index: 1 },
+        session_id: 0,
+    };
+
+    // a route at the limit is forwarded, a longer one is refused
+    send_packet_to_drone(&env, d_id, fragment_along(vec![c_id, d_id, s_id, 1]));
+    let mut expected = fragment_along(vec![c_id, d_id, s_id, 1]);
+    expected.routing_header.hop_index = 2;
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected
+    );
+
+    send_packet_to_drone(&env, d_id, fragment_along(vec![c_id, d_id, s_id, 1, 2]));
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        Packet {
+            pack_type: PacketType::Nack(Nack {
+                fragment_index: 0,
+                nack_type: NackType::ErrorInRouting(s_id),
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![d_id, c_id],
+                hop_index: 1,
+            },
+            session_id: 0,
+        }
+    );
+    assert!(s_recv.try_recv().is_err());
+
+    let event_recv = env.get(&d_id).unwrap().event_recv();
+    assert!(event_recv
+        .try_iter()
+        .any(|e| e
+            == RustDroneEvent::RouteTooLong(d_id, fragment_along(vec![c_id, d_id, s_id, 1, 2]))));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_runs_packet_hooks() {
+    // counts the packets it sees, vetoes the second fragment and tags forwarded packets
+    struct Tagger(Arc<AtomicUsize>);
+
+    impl PacketHook for Tagger {
+        fn on_packet(&mut self, _drone_id: NodeId, packet: &mut Packet) -> HookVerdict {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            match &packet.pack_type {
+                PacketType::MsgFragment(fragment) if fragment.fragment_index == 1 => {
+                    HookVerdict::Veto
+                }
+                _ => HookVerdict::Pass,
+            }
+        }
+
+        fn on_forward(
+            &mut self,
+            drone_id: NodeId,
+            _next_hop: NodeId,
+            packet: &mut Packet,
+        ) -> HookVerdict {
+            packet.session_id = drone_id as u64;
+            HookVerdict::Pass
+        }
+    }
+
+    let d_id = 7;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+    let seen = Arc::new(AtomicUsize::new(0));
+
+    let hook_seen = seen.clone();
+    let (_, env) = provision_customized_drones_from_config(&config, move |_, drone| {
+        drone.with_hook(Box::new(Tagger(hook_seen.clone())))
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment = |fragment_index| Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index,
+            total_n_fragments: 2,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    send_packet_to_drone(&env, d_id, fragment(0));
+    send_packet_to_drone(&env, d_id, fragment(1));
+
+    let mut expected = fragment(0);
+    expected.routing_header.hop_index = 2;
+    expected.session_id = d_id as u64;
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected
+    );
+    assert!(s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).is_err());
+    assert_eq!(seen.load(Ordering::SeqCst), 2);
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_returns_nack_if_next_hop_channel_is_closed() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send.clone()));
+    drop(s_recv);
+
+    let session_id = rand::random::<u64>();
+    let (payload_len, payload) = generate_random_payload();
+
+    let sending_packet = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    send_packet_to_drone(&env, d_id, sending_packet);
+
+    let expected_packet = Packet {
+        pack_type: PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::ErrorInRouting(s_id),
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![d_id, c_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected_packet
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_returns_nack_if_next_hop_queue_is_full() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = bounded(1);
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send.clone()));
+
+    let session_id = rand::random::<u64>();
+    let (payload_len, payload) = generate_random_payload();
+
+    let sending_packet = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    // fill the queue of the next hop
+    s_send.send(sending_packet.clone()).unwrap();
+    send_packet_to_drone(&env, d_id, sending_packet.clone());
+
+    let expected_packet = Packet {
+        pack_type: PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::Dropped,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![d_id, c_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected_packet
+    );
+
+    let mut overflowed_packet = sending_packet;
+    overflowed_packet.routing_header.hop_index = 2;
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::QueueOverflow(d_id, s_id, overflowed_packet)
+    );
+    assert_eq!(s_recv.len(), 1);
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn undeliverable_control_packets_use_shortcut() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    let (s_send, s_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (controller_recv, env) = provision_drones_from_config(&config);
+
+    // the link to the server breaks, while node 1 was never connected at all
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+    drop(s_recv);
+
+    let control_packets = [
+        PacketType::Ack(Ack { fragment_index: 0 }),
+        PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::Dropped,
+        }),
+        PacketType::FloodResponse(FloodResponse {
+            flood_id: 0,
+            path_trace: vec![(s_id, NodeType::Server)],
+        }),
+    ];
+
+    for next_hop in [1, s_id] {
+        for pack_type in control_packets.iter().cloned() {
+            let sending_packet = Packet {
+                pack_type,
+                routing_header: SourceRoutingHeader {
+                    hops: vec![c_id, d_id, next_hop],
+                    hop_index: 1,
+                },
+                session_id: rand::random::<u64>(),
+            };
+
+            send_packet_to_drone(&env, d_id, sending_packet.clone());
+
+            let shortcut =
+                std::iter::from_fn(|| controller_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).ok())
+                    .find(|event| matches!(event, DroneEvent::ControllerShortcut(_)))
+                    .expect("Drone did not use the controller shortcut");
+
+            assert_eq!(shortcut, DroneEvent::ControllerShortcut(sending_packet));
+        }
+    }
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_returns_nack_if_unexpected_recipient() {
+    let d_id = 0;
+    let c_id = 100;
+    let mut config = HashMap::new();
+    let (c_send, c_recv) = unbounded();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+
+    let session_id = rand::random::<u64>();
+    let (payload_len, payload) = generate_random_payload();
+
+    let sending_packet = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, 99, 1],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    send_packet_to_drone(&env, d_id, sending_packet.clone());
+
+    let expected_packet = Packet {
+        pack_type: PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::UnexpectedRecipient(d_id),
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![d_id, c_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected_packet
+    );
+
+    terminate_env(env, config);
+}
+
+fn malformed_packets() -> Vec<Packet> {
+    let (payload_len, payload) = generate_random_payload();
+
+    let fragment = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: Vec::new(),
+            hop_index: 0,
+        },
+        session_id: rand::random::<u64>(),
+    };
+
+    let mut past_the_end = fragment.clone();
+    past_the_end.routing_header = SourceRoutingHeader {
+        hops: vec![100, 0],
+        hop_index: 2,
+    };
+
+    let mut ack = fragment.clone();
+    ack.pack_type = PacketType::Ack(Ack { fragment_index: 0 });
+
+    vec![fragment, past_the_end, ack]
+}
+
+#[test]
+fn drone_reports_malformed_packets() {
+    let d_id = 0;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (controller_recv, env) = provision_drones_from_config(&config);
+
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
+
+    for packet in malformed_packets() {
+        send_packet_to_drone(&env, d_id, packet.clone());
+
+        assert_eq!(
+            recv_event_from_drone(&env, d_id),
+            RustDroneEvent::MalformedPacket(d_id, packet)
+        );
+    }
+    assert!(controller_recv.try_recv().is_err());
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn crashing_drone_reports_malformed_packets() {
+    let d_id = 0;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::Crash);
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Crashing(d_id)
+    );
+
+    for packet in malformed_packets() {
+        send_packet_to_drone(&env, d_id, packet.clone());
+
+        assert_eq!(
+            recv_event_from_drone(&env, d_id),
+            RustDroneEvent::MalformedPacket(d_id, packet)
+        );
+    }
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_shortcuts_malformed_packets_if_enabled() {
+    let d_id = 0;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+
+    let (controller_recv, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_malformed_packet_policy(MalformedPacketPolicy::Shortcut)
+    });
+
+    for packet in malformed_packets() {
+        send_packet_to_drone(&env, d_id, packet.clone());
+
+        assert_eq!(
+            controller_recv
+                .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+                .unwrap(),
+            DroneEvent::ControllerShortcut(packet)
+        );
+    }
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_drops_malformed_packets_silently_if_asked() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, s_recv) = unbounded();
+
+    let (controller_recv, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_malformed_packet_policy(MalformedPacketPolicy::Drop)
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    for packet in malformed_packets() {
+        send_packet_to_drone(&env, d_id, packet);
+    }
+
+    // a well formed packet sent afterwards is handled once the malformed ones are gone
+    let (payload_len, payload) = generate_random_payload();
+    let mut fragment = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    send_packet_to_drone(&env, d_id, fragment.clone());
+
+    fragment.routing_header.hop_index = 2;
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        fragment
+    );
+
+    let event_recv = env.get(&d_id).unwrap().event_recv();
+    assert!(!event_recv
+        .try_iter()
+        .any(|e| matches!(e, RustDroneEvent::MalformedPacket(..))));
+    assert!(!controller_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::ControllerShortcut(_))));
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_forwards_fragment() {
+    let mut config = HashMap::new();
+    config.insert(11, (0.0, vec![]));
+    let (d2_send, d2_recv) = unbounded();
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, 11, DroneCommand::AddSender(12, d2_send.clone()));
+
+    let (payload_len, payload) = generate_random_payload();
+    let session_id = rand::random::<u64>();
+
+    let sending_packet = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![11, 12],
+            hop_index: 0,
+        },
+        session_id,
+    };
+
+    // Send the packet to the drone
+    send_packet_to_drone(&env, 11, sending_packet.clone());
+
+    let mut expected_packet = sending_packet;
+    // The packet should have been forwarded to the neighbour
+    expected_packet.routing_header.hop_index = 1;
+
+    assert_eq!(
+        d2_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
         expected_packet
     );
 
-    terminate_env(env, config);
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_delays_packets_by_processing_time() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let processing_time = Duration::from_millis(50);
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, s_recv) = unbounded();
+
+    let (_, env) = provision_customized_drones_from_config(&config, move |_, drone| {
+        drone.with_processing_delay(ProcessingDelay::Constant(processing_time))
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send.clone()));
+
+    let mut msg = Packet {
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: rand::random::<u64>(),
+    };
+
+    let start_time = Instant::now();
+    send_packet_to_drone(&env, d_id, msg.clone());
+    send_packet_to_drone(&env, d_id, msg.clone());
+
+    msg.routing_header.hop_index = 2;
+
+    // packets are processed one after the other
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        msg.clone()
+    );
+    assert!(start_time.elapsed() >= processing_time);
+
+    assert_eq!(s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(), msg);
+    assert!(start_time.elapsed() >= processing_time * 2);
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_applies_link_pdr_to_single_neighbour() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let t_id = 201;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+    let (t_send, t_recv) = unbounded();
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(t_id, t_send));
+    send_rust_command_to_drone(
+        &env,
+        d_id,
+        RustDroneCommand::SetLinkQuality(
+            s_id,
+            LinkQuality {
+                pdr: Some(1.0),
+                ..Default::default()
+            },
+        ),
+    );
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment_to = |dest: NodeId| Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, dest],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+
+    // only the lossy link drops the fragment
+    send_packet_to_drone(&env, d_id, fragment_to(s_id));
+    send_packet_to_drone(&env, d_id, fragment_to(t_id));
+
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        Packet {
+            pack_type: PacketType::Nack(Nack {
+                fragment_index: 0,
+                nack_type: NackType::Dropped,
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![d_id, c_id],
+                hop_index: 1,
+            },
+            session_id: 0,
+        }
+    );
+    assert!(s_recv.try_recv().is_err());
+
+    let mut expected = fragment_to(t_id);
+    expected.routing_header.hop_index = 2;
+    assert_eq!(
+        t_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_link_pdr_overrides_drone_pdr() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (1.0, vec![]));
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+
+    let (_, env) = provision_customized_drones_from_config(&config, move |_, drone| {
+        drone.with_link_pdr(s_id, 0.0)
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let msg = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: rand::random::<u64>(),
+    };
+
+    send_packet_to_drone(&env, d_id, msg.clone());
+
+    let mut expected = msg.clone();
+    expected.routing_header.hop_index = 2;
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        expected
+    );
+
+    send_rust_command_to_drone(&env, d_id, RustDroneCommand::SetLinkPdr(s_id, 1.0));
+    send_packet_to_drone(&env, d_id, msg.clone());
+
+    assert!(matches!(
+        c_recv
+            .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+            .unwrap()
+            .pack_type,
+        PacketType::Nack(Nack {
+            nack_type: NackType::Dropped,
+            ..
+        })
+    ));
+    assert!(s_recv.try_recv().is_err());
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn drone_delays_packets_by_link_latency_and_bandwidth() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let latency = Duration::from_millis(30);
+    let transmission_time = Duration::from_millis(40);
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, s_recv) = unbounded();
+
+    let (payload_len, payload) = generate_random_payload();
+    let bandwidth = (payload_len as f64 * 8.0 / transmission_time.as_secs_f64()) as u64;
+
+    let (_, env) = provision_customized_drones_from_config(&config, move |_, drone| {
+        drone.with_link_quality(
+            s_id,
+            LinkQuality {
+                pdr: None,
+                latency,
+                bandwidth: Some(bandwidth),
+            },
+        )
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let mut msg = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: rand::random::<u64>(),
+    };
+
+    let start_time = Instant::now();
+    send_packet_to_drone(&env, d_id, msg.clone());
+    send_packet_to_drone(&env, d_id, msg.clone());
+
+    msg.routing_header.hop_index = 2;
+
+    // the second fragment waits for the first one to be transmitted
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        msg.clone()
+    );
+    assert!(start_time.elapsed() >= transmission_time + latency);
+
+    assert_eq!(s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(), msg);
+    assert!(start_time.elapsed() >= transmission_time * 2 + latency);
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn virtual_clock_skips_link_latency_instantly() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let latency = Duration::from_secs(3600);
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, s_recv) = unbounded();
+    let clock = VirtualClock::new();
+
+    let drone_clock = clock.clone();
+    let (_, env) = provision_customized_drones_from_config(&config, move |_, drone| {
+        drone
+            .with_clock(Box::new(drone_clock.clone()))
+            .with_link_quality(
+                s_id,
+                LinkQuality {
+                    pdr: None,
+                    latency,
+                    bandwidth: None,
+                },
+            )
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let mut msg = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: rand::random::<u64>(),
+    };
+    send_packet_to_drone(&env, d_id, msg.clone());
+
+    // wait for the drone to put the fragment in transit
+    let start_time = Instant::now();
+    while clock.next_deadline().is_none() {
+        assert!(start_time.elapsed() < MAX_PACKET_WAIT_TIMEOUT);
+        thread::yield_now();
+    }
+
+    clock.advance(latency - Duration::from_secs(1));
+    assert!(s_recv.recv_timeout(Duration::from_millis(20)).is_err());
+
+    clock.advance(Duration::from_secs(1));
+    msg.routing_header.hop_index = 2;
+    assert_eq!(s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(), msg);
+    assert!(start_time.elapsed() < latency);
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn virtual_clock_keeps_one_timer_per_clone() {
+    let clock = VirtualClock::new();
+    let drone_clock = clock.clone();
+    let other_clock = clock.clone();
+    let start = clock.now();
+
+    // a drone asks for a new timer every time it goes back to waiting
+    for secs in 1..=100 {
+        drop(drone_clock.at(start + Duration::from_secs(secs)));
+    }
+    let drone_timer = drone_clock.at(start + Duration::from_secs(5));
+    let other_timer = other_clock.at(start + Duration::from_secs(3));
+    assert_eq!(clock.next_deadline(), Some(start + Duration::from_secs(3)));
+
+    clock.advance(Duration::from_secs(3));
+    assert!(other_timer.try_recv().is_ok());
+    assert!(drone_timer.try_recv().is_err());
+    assert_eq!(clock.next_deadline(), Some(start + Duration::from_secs(5)));
+
+    clock.advance(Duration::from_secs(2));
+    assert!(drone_timer.try_recv().is_ok());
+    assert_eq!(clock.next_deadline(), None);
+}
+
+#[test]
+fn rate_limited_drone_queues_then_drops_packets() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (c_send, c_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+    let clock = VirtualClock::new();
+
+    let drone_clock = clock.clone();
+    let (controller_recv, env) =
+        provision_customized_drones_from_config(&config, move |_, drone| {
+            drone
+                .with_clock(Box::new(drone_clock.clone()))
+                .with_rate_limit(RateLimit {
+                    packets_per_second: 1.0,
+                    burst: 1,
+                    queue_capacity: 1,
+                })
+        });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let (payload_len, payload) = generate_random_payload();
+    let fragment = |fragment_index| Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index,
+            total_n_fragments: 3,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    let forwarded = |fragment_index| {
+        let mut packet = fragment(fragment_index);
+        packet.routing_header.hop_index = 2;
+        packet
+    };
+    for fragment_index in 0..3 {
+        send_packet_to_drone(&env, d_id, fragment(fragment_index));
+    }
+
+    // the first fragment takes the only token, the second waits and the third is dropped
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        forwarded(0)
+    );
+    assert_eq!(
+        c_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        Packet {
+            pack_type: PacketType::Nack(Nack {
+                fragment_index: 2,
+                nack_type: NackType::Dropped,
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![d_id, c_id],
+                hop_index: 1,
+            },
+            session_id: 0,
+        }
+    );
+    assert!(s_recv.try_recv().is_err());
+
+    let event_recv = env.get(&d_id).unwrap().event_recv();
+    assert!(event_recv
+        .try_iter()
+        .any(|e| e == RustDroneEvent::RateLimited(d_id, fragment(2))));
+
+    // control packets over the limit must not be lost either
+    let ack = Packet {
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id: 0,
+    };
+    send_packet_to_drone(&env, d_id, ack.clone());
+    assert!(
+        std::iter::from_fn(|| controller_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).ok())
+            .any(|e| matches!(e, DroneEvent::ControllerShortcut(p) if p == ack))
+    );
+    // and since the controller delivers it, it is not reported as dropped
+    send_rust_command_to_drone(&env, d_id, RustDroneCommand::Sync(0));
+    assert!(
+        std::iter::from_fn(|| Some(recv_event_from_drone(&env, d_id)))
+            .any(|event| event == RustDroneEvent::Synced(d_id, 0))
+    );
+    assert!(!controller_recv
+        .try_iter()
+        .any(|e| matches!(e, DroneEvent::PacketDropped(p) if p == ack)));
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        forwarded(1)
+    );
+
+    assert_eq!(request_stats(&env, d_id).rate_limited, 2);
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn rate_limiter_holds_at_least_one_token() {
+    let mut rate_limiter = RateLimiter::new(RateLimit {
+        packets_per_second: 1.0,
+        burst: 0,
+        queue_capacity: 1,
+    });
+    let start = Instant::now();
+
+    assert!(rate_limiter.try_take(start));
+    assert!(!rate_limiter.try_take(start));
+    assert!(rate_limiter.try_take(start + Duration::from_secs(1)));
+}
+
+#[test]
+fn drone_sends_heartbeats() {
+    let d_id = 0;
+    let interval = Duration::from_secs(1);
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let clock = VirtualClock::new();
+
+    let drone_clock = clock.clone();
+    let (_, env) = provision_customized_drones_from_config(&config, move |_, drone| {
+        drone
+            .with_clock(Box::new(drone_clock.clone()))
+            .with_heartbeat(interval)
+    });
+
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
+
+    for beat in 1..=2 {
+        clock.advance(interval);
+        assert_eq!(
+            recv_event_from_drone(&env, d_id),
+            RustDroneEvent::Heartbeat(
+                d_id,
+                Heartbeat {
+                    uptime: interval * beat,
+                    queue_depth: 0,
+                }
+            )
+        );
+    }
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn heartbeat_watchdog_flags_silent_drones() {
+    let timeout = Duration::from_secs(1);
+    let heartbeat = Heartbeat {
+        uptime: Duration::ZERO,
+        queue_depth: 0,
+    };
+    let start = Instant::now();
+    let mut watchdog = HeartbeatWatchdog::new(timeout);
+
+    watchdog.record(&RustDroneEvent::Started(1), start);
+    watchdog.record(&RustDroneEvent::Started(2), start);
+    watchdog.record(&RustDroneEvent::Started(3), start);
+    assert!(watchdog.silent_drones(start + timeout).is_empty());
+
+    // drone 2 keeps beating and drone 3 stops on purpose, only drone 1 is stuck
+    watchdog.record(&RustDroneEvent::Heartbeat(2, heartbeat), start + timeout);
+    watchdog.record(&RustDroneEvent::Stopped(3), start + timeout);
+    assert_eq!(watchdog.silent_drones(start + timeout * 2), vec![1]);
+}
+
+#[test]
+fn heartbeat_watchdog_counts_any_event_as_sign_of_life() {
+    let timeout = Duration::from_secs(1);
+    let start = Instant::now();
+    let mut watchdog = HeartbeatWatchdog::new(timeout);
+
+    watchdog.record(&RustDroneEvent::Started(1), start);
+    watchdog.record(&RustDroneEvent::Started(2), start);
+
+    // neither drone sends heartbeats, only drone 2 answers the ping
+    watchdog.record(&RustDroneEvent::Synced(2, 0), start + timeout);
+    assert_eq!(watchdog.silent_drones(start + timeout * 2), vec![1]);
+
+    watchdog.record(&RustDroneEvent::Paused(1), start + timeout * 2);
+    assert!(watchdog.silent_drones(start + timeout * 2).is_empty());
+}
+
+#[test]
+fn control_packets_are_not_slowed_down_by_link_bandwidth() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (s_send, s_recv) = unbounded();
+    let clock = VirtualClock::new();
+
+    let (payload_len, payload) = generate_random_payload();
+    // a fragment takes a second to be transmitted
+    let bandwidth = payload_len as u64 * 8;
+
+    let drone_clock = clock.clone();
+    let (_, env) = provision_customized_drones_from_config(&config, move |_, drone| {
+        drone
+            .with_clock(Box::new(drone_clock.clone()))
+            .with_link_quality(
+                s_id,
+                LinkQuality {
+                    pdr: None,
+                    latency: Duration::ZERO,
+                    bandwidth: Some(bandwidth),
+                },
+            )
+    });
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    let routing_header = SourceRoutingHeader {
+        hops: vec![c_id, d_id, s_id],
+        hop_index: 1,
+    };
+    let mut fragment = Packet {
+        pack_type: PacketType::MsgFragment(Fragment {
+            fragment_index: 0,
+            total_n_fragments: 1,
+            length: payload_len,
+            data: payload,
+        }),
+        routing_header: routing_header.clone(),
+        session_id: 0,
+    };
+    let mut ack = Packet {
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        routing_header,
+        session_id: 1,
+    };
+    send_packet_to_drone(&env, d_id, fragment.clone());
+    send_packet_to_drone(&env, d_id, ack.clone());
+
+    // the ack overtakes the fragment still being transmitted
+    ack.routing_header.hop_index = 2;
+    assert_eq!(s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(), ack);
+    assert!(s_recv.try_recv().is_err());
+
+    clock.advance(Duration::from_secs(1));
+    fragment.routing_header.hop_index = 2;
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        fragment
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn seeded_drones_drop_the_same_fragments() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
+    let seed = rand::random::<u64>();
+    println!("Seed: {}", seed);
+
+    let drop_pattern = || {
+        let mut config = HashMap::new();
+        config.insert(d_id, (0.5, vec![]));
+        // both neighbours share a channel, so every fragment is answered by a single packet
+        let (n_send, n_recv) = unbounded();
+
+        let (_, env) =
+            provision_customized_drones_from_config(&config, move |_, drone| drone.with_seed(seed));
+
+        send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, n_send.clone()));
+        send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, n_send));
+
+        let (payload_len, payload) = generate_random_payload();
+        let pattern = (0..32)
+            .map(|i| {
+                send_packet_to_drone(
+                    &env,
+                    d_id,
+                    Packet {
+                        pack_type: PacketType::MsgFragment(Fragment {
+                            fragment_index: i,
+                            total_n_fragments: 32,
+                            length: payload_len,
+                            data: payload,
+                        }),
+                        routing_header: SourceRoutingHeader {
+                            hops: vec![c_id, d_id, s_id],
+                            hop_index: 1,
+                        },
+                        session_id: 0,
+                    },
+                );
+                let packet = n_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap();
+                matches!(packet.pack_type, PacketType::MsgFragment(_))
+            })
+            .collect::<Vec<_>>();
+
+        terminate_env(env, config);
+        pattern
+    };
+
+    assert_eq!(drop_pattern(), drop_pattern());
 }
 
 #[test]
-fn drone_forwards_fragment() {
+fn ack_messages_are_not_affected_by_pdr() {
+    let d_id = 0;
+    let c_id = 100;
+    let s_id = 200;
     let mut config = HashMap::new();
-    config.insert(11, (0.0, vec![]));
-    let (d2_send, d2_recv) = unbounded();
+    config.insert(d_id, (1.0, vec![]));
+    let (c_send, _) = unbounded();
+    let (s_send, s_recv) = unbounded();
 
     let (_, env) = provision_drones_from_config(&config);
 
-    send_command_to_drone(&env, 11, DroneCommand::AddSender(12, d2_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send.clone()));
 
-    let (payload_len, payload) = generate_random_payload();
     let session_id = rand::random::<u64>();
 
     let sending_packet = Packet {
-        pack_type: PacketType::MsgFragment(Fragment {
-            fragment_index: 0,
-            total_n_fragments: 1,
-            length: payload_len,
-            data: payload,
-        }),
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
         routing_header: SourceRoutingHeader {
-            hops: vec![11, 12],
-            hop_index: 0,
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
         },
         session_id,
     };
 
     // Send the packet to the drone
-    send_packet_to_drone(&env, 11, sending_packet.clone());
+    send_packet_to_drone(&env, d_id, sending_packet.clone());
 
     let mut expected_packet = sending_packet;
     // The packet should have been forwarded to the neighbour
-    expected_packet.routing_header.hop_index = 1;
+    expected_packet.routing_header.hop_index = 2;
 
     assert_eq!(
-        d2_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
         expected_packet
     );
 
@@ -332,7 +3156,7 @@ fn drone_forwards_fragment() {
 }
 
 #[test]
-fn ack_messages_are_not_affected_by_pdr() {
+fn nack_messages_are_not_affected_by_pdr() {
     let d_id = 0;
     let c_id = 100;
     let s_id = 200;
@@ -349,7 +3173,10 @@ fn ack_messages_are_not_affected_by_pdr() {
     let session_id = rand::random::<u64>();
 
     let sending_packet = Packet {
-        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        pack_type: PacketType::Nack(Nack {
+            fragment_index: 0,
+            nack_type: NackType::Dropped,
+        }),
         routing_header: SourceRoutingHeader {
             hops: vec![c_id, d_id, s_id],
             hop_index: 1,
@@ -373,23 +3200,101 @@ fn ack_messages_are_not_affected_by_pdr() {
 }
 
 #[test]
-fn nack_messages_are_not_affected_by_pdr() {
+fn every_nth_drop_model_is_deterministic() {
+    let fragment = Fragment {
+        fragment_index: 0,
+        total_n_fragments: 1,
+        length: 128,
+        data: [0; 128],
+    };
+    let mut rng = rand::rng();
+    let mut model = EveryNth::new(3);
+
+    let drops = (0..6)
+        .map(|_| model.should_drop(&fragment, &mut rng))
+        .collect::<Vec<_>>();
+    assert_eq!(drops, vec![false, false, true, false, false, true]);
+
+    // a PDR of 0.5 drops every other fragment, 0 never drops
+    model.set_pdr(0.5);
+    let drops = (0..4)
+        .map(|_| model.should_drop(&fragment, &mut rng))
+        .collect::<Vec<_>>();
+    assert_eq!(drops, vec![false, true, false, true]);
+
+    model.set_pdr(0.0);
+    assert!((0..10).all(|_| !model.should_drop(&fragment, &mut rng)));
+}
+
+#[test]
+fn gilbert_elliott_drop_model_drops_in_bursts() {
+    let fragment = Fragment {
+        fragment_index: 0,
+        total_n_fragments: 1,
+        length: 128,
+        data: [0; 128],
+    };
+    let mut rng = rand::rng();
+
+    // the good state never drops and is never left
+    let mut model = GilbertElliott::new(0.0, 1.0, 0.0, 1.0);
+    assert!((0..32).all(|_| !model.should_drop(&fragment, &mut rng)));
+
+    // the bad state is entered right away and never left
+    let mut model = GilbertElliott::new(1.0, 0.0, 0.0, 1.0);
+    assert!((0..32).all(|_| model.should_drop(&fragment, &mut rng)));
+
+    // the state alternates at every fragment
+    let mut model = GilbertElliott::new(1.0, 1.0, 0.0, 1.0);
+    let drops = (0..4)
+        .map(|_| model.should_drop(&fragment, &mut rng))
+        .collect::<Vec<_>>();
+    assert_eq!(drops, vec![true, false, true, false]);
+}
+
+#[test]
+fn non_conformant_drone_drops_control_packets() {
     let d_id = 0;
     let c_id = 100;
     let s_id = 200;
     let mut config = HashMap::new();
-    config.insert(d_id, (1.0, vec![]));
+    config.insert(d_id, (0.0, vec![]));
     let (c_send, _) = unbounded();
     let (s_send, s_recv) = unbounded();
 
-    let (_, env) = provision_drones_from_config(&config);
+    let (controller_recv, env) = provision_customized_drones_from_config(&config, |_, drone| {
+        drone.with_non_conformant_control_pdr(ControlPdr {
+            ack: 1.0,
+            ..Default::default()
+        })
+    });
 
     send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
     send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send.clone()));
 
     let session_id = rand::random::<u64>();
 
-    let sending_packet = Packet {
+    let ack = Packet {
+        pack_type: PacketType::Ack(Ack { fragment_index: 0 }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![c_id, d_id, s_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    send_packet_to_drone(&env, d_id, ack.clone());
+
+    // the Ack is lost, without being shortcut to the controller
+    assert_eq!(
+        controller_recv
+            .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+            .unwrap(),
+        DroneEvent::PacketDropped(ack)
+    );
+    assert!(s_recv.try_recv().is_err());
+
+    let mut nack = Packet {
         pack_type: PacketType::Nack(Nack {
             fragment_index: 0,
             nack_type: NackType::Dropped,
@@ -401,17 +3306,11 @@ fn nack_messages_are_not_affected_by_pdr() {
         session_id,
     };
 
-    // Send the packet to the drone
-    send_packet_to_drone(&env, d_id, sending_packet.clone());
-
-    let mut expected_packet = sending_packet;
-    // The packet should have been forwarded to the neighbour
-    expected_packet.routing_header.hop_index = 2;
+    // Nacks are still delivered
+    send_packet_to_drone(&env, d_id, nack.clone());
 
-    assert_eq!(
-        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
-        expected_packet
-    );
+    nack.routing_header.hop_index = 2;
+    assert_eq!(s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(), nack);
 
     terminate_env(env, config);
 }
@@ -669,6 +3568,300 @@ fn return_flood_response_with_one_neighbour() {
     terminate_env(env, config);
 }
 
+#[test]
+fn flood_response_uses_shortcut_if_sender_was_removed() {
+    let d_id = 11;
+    let c_id = 1;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (c_send, c_recv) = unbounded();
+
+    let (controller_recv, env) = provision_drones_from_config(&config);
+
+    // the client disconnects right after sending its flood request
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::RemoveSender(c_id));
+
+    let session_id = rand::random::<u64>();
+    let flood_id = rand::random::<u64>();
+
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::FloodRequest(FloodRequest {
+                flood_id,
+                initiator_id: c_id,
+                path_trace: vec![(c_id, NodeType::Client)],
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: Vec::new(),
+                hop_index: 0,
+            },
+            session_id,
+        },
+    );
+
+    let expected_packet = Packet {
+        pack_type: PacketType::FloodResponse(FloodResponse {
+            flood_id,
+            path_trace: vec![(c_id, NodeType::Client), (d_id, NodeType::Drone)],
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![d_id, c_id],
+            hop_index: 0,
+        },
+        session_id,
+    };
+
+    assert_eq!(
+        controller_recv
+            .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+            .unwrap(),
+        DroneEvent::ControllerShortcut(expected_packet)
+    );
+    assert!(c_recv.try_recv().is_err());
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn flood_response_uses_shortcut_if_sender_channel_is_closed() {
+    let d_id = 11;
+    let c_id = 1;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (c_send, c_recv) = unbounded();
+
+    let (controller_recv, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    // the client goes away before the response comes back
+    drop(c_recv);
+
+    let session_id = rand::random::<u64>();
+    let flood_id = rand::random::<u64>();
+
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::FloodRequest(FloodRequest {
+                flood_id,
+                initiator_id: c_id,
+                path_trace: vec![(c_id, NodeType::Client)],
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: Vec::new(),
+                hop_index: 0,
+            },
+            session_id,
+        },
+    );
+
+    let expected_packet = Packet {
+        pack_type: PacketType::FloodResponse(FloodResponse {
+            flood_id,
+            path_trace: vec![(c_id, NodeType::Client), (d_id, NodeType::Drone)],
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![d_id, c_id],
+            hop_index: 0,
+        },
+        session_id,
+    };
+
+    assert_eq!(
+        controller_recv
+            .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+            .unwrap(),
+        DroneEvent::ControllerShortcut(expected_packet)
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn flood_request_is_forwarded_if_sender_was_removed() {
+    let c_id = 1;
+    let mut config = HashMap::new();
+    config.insert(11, (0.0, vec![12]));
+    config.insert(12, (0.0, vec![11]));
+    let (c_send, c_recv) = unbounded();
+
+    let (controller_recv, env) = provision_drones_from_config(&config);
+
+    // 11 is left with 12 as its only neighbour, the flood must still reach it
+    send_command_to_drone(&env, 11, DroneCommand::AddSender(c_id, c_send.clone()));
+    send_command_to_drone(&env, 11, DroneCommand::RemoveSender(c_id));
+
+    let session_id = rand::random::<u64>();
+    let flood_id = rand::random::<u64>();
+
+    send_packet_to_drone(
+        &env,
+        11,
+        Packet {
+            pack_type: PacketType::FloodRequest(FloodRequest {
+                flood_id,
+                initiator_id: c_id,
+                path_trace: vec![(c_id, NodeType::Client)],
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: Vec::new(),
+                hop_index: 0,
+            },
+            session_id,
+        },
+    );
+
+    let expected_packet = Packet {
+        pack_type: PacketType::FloodResponse(FloodResponse {
+            flood_id,
+            path_trace: vec![
+                (c_id, NodeType::Client),
+                (11, NodeType::Drone),
+                (12, NodeType::Drone),
+            ],
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: vec![12, 11, c_id],
+            hop_index: 1,
+        },
+        session_id,
+    };
+
+    // the response reaches 11 but can't go further than that
+    let shortcut =
+        std::iter::from_fn(|| controller_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).ok())
+            .find(|event| matches!(event, DroneEvent::ControllerShortcut(_)))
+            .unwrap();
+
+    assert_eq!(shortcut, DroneEvent::ControllerShortcut(expected_packet));
+    assert!(c_recv.try_recv().is_err());
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn flood_request_to_closed_channel_does_not_crash_drone() {
+    let d_id = 11;
+    let c_id = 1;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (c_send, _c_recv) = unbounded();
+    let (x_send, x_recv) = unbounded();
+
+    let (controller_recv, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(c_id, c_send.clone()));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(2, x_send.clone()));
+    drop(x_recv);
+
+    send_packet_to_drone(
+        &env,
+        d_id,
+        Packet {
+            pack_type: PacketType::FloodRequest(FloodRequest {
+                flood_id: rand::random::<u64>(),
+                initiator_id: c_id,
+                path_trace: vec![(c_id, NodeType::Client)],
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: Vec::new(),
+                hop_index: 0,
+            },
+            session_id: rand::random::<u64>(),
+        },
+    );
+
+    // the drone survives and forgets about the closed channel
+    assert!(matches!(
+        controller_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT),
+        Ok(DroneEvent::PacketDropped(_))
+    ));
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Started(d_id)
+    );
+    send_rust_command_to_drone(&env, d_id, RustDroneCommand::QueryNeighbours);
+    assert_eq!(
+        recv_event_from_drone(&env, d_id),
+        RustDroneEvent::Neighbours(d_id, vec![c_id])
+    );
+
+    terminate_env(env, config);
+}
+
+#[test]
+fn concurrent_floods_with_same_id_are_kept_apart() {
+    let d_id = 11;
+    let n_id = 12;
+    let s_id = 13;
+    let c1_id = 100;
+    let c2_id = 101;
+    let mut config = HashMap::new();
+    config.insert(d_id, (0.0, vec![]));
+    let (n_send, n_recv) = unbounded();
+    let (s_send, s_recv) = unbounded();
+
+    let (_, env) = provision_drones_from_config(&config);
+
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(n_id, n_send));
+    send_command_to_drone(&env, d_id, DroneCommand::AddSender(s_id, s_send));
+
+    // both clients happen to pick the same flood id and leave themselves out of the path
+    // trace, so only the initiator tells the floods relayed by the same neighbour apart
+    let flood_id = rand::random::<u64>();
+    let flood_request_from = |c_id: NodeId, path_trace: Vec<(NodeId, NodeType)>| Packet {
+        pack_type: PacketType::FloodRequest(FloodRequest {
+            flood_id,
+            initiator_id: c_id,
+            path_trace,
+        }),
+        routing_header: SourceRoutingHeader {
+            hops: Vec::new(),
+            hop_index: 0,
+        },
+        session_id: c_id as u64,
+    };
+    let received = vec![(n_id, NodeType::Drone)];
+    let forwarded = vec![(n_id, NodeType::Drone), (d_id, NodeType::Drone)];
+
+    send_packet_to_drone(&env, d_id, flood_request_from(c1_id, received.clone()));
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        flood_request_from(c1_id, forwarded.clone())
+    );
+
+    // the second flood is new to the drone, so it is forwarded instead of answered
+    send_packet_to_drone(&env, d_id, flood_request_from(c2_id, received.clone()));
+    assert_eq!(
+        s_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        flood_request_from(c2_id, forwarded.clone())
+    );
+    assert!(n_recv.try_recv().is_err());
+
+    // a flood seen before is still answered right away
+    send_packet_to_drone(&env, d_id, flood_request_from(c1_id, received));
+    assert_eq!(
+        n_recv.recv_timeout(MAX_PACKET_WAIT_TIMEOUT).unwrap(),
+        Packet {
+            pack_type: PacketType::FloodResponse(FloodResponse {
+                flood_id,
+                path_trace: forwarded,
+            }),
+            routing_header: SourceRoutingHeader {
+                hops: vec![d_id, n_id],
+                hop_index: 1,
+            },
+            session_id: c1_id as u64,
+        }
+    );
+
+    terminate_env(env, config);
+}
+
 #[test]
 fn flood_request_on_big_network() {
     let (seed, config) = generate_random_config();
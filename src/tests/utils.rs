@@ -1,12 +1,15 @@
+use super::super::controller::{DroneSnapshot, RustDroneCommand, RustDroneEvent};
 use super::super::drone::*;
+use super::super::stats::DroneStats;
 use super::*;
 
 use crossbeam::channel::{unbounded, Receiver, Sender};
 use log4rs_test_utils::test_logging::init_logging_once_for;
 use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use wg_2024::controller::{DroneCommand, DroneEvent};
 use wg_2024::drone::Drone;
@@ -14,7 +17,95 @@ use wg_2024::network::NodeId;
 use wg_2024::packet::{Packet, PacketType};
 
 type Config = HashMap<NodeId, (f32, Vec<NodeId>)>;
-type Environment = HashMap<NodeId, (thread::JoinHandle<()>, Sender<Packet>, Sender<DroneCommand>)>;
+type Environment = HashMap<NodeId, NodeHandle>;
+
+pub struct NodeHandle {
+    thread: thread::JoinHandle<()>,
+    packet_send: Sender<Packet>,
+    command_send: Sender<DroneCommand>,
+    rust_command_send: Sender<RustDroneCommand>,
+    event_recv: Receiver<RustDroneEvent>,
+}
+
+impl NodeHandle {
+    pub fn command(&self, command: DroneCommand) {
+        self.command_send
+            .send(command)
+            .expect("Failed to send command to drone");
+    }
+
+    pub fn rust_command(&self, command: RustDroneCommand) {
+        self.rust_command_send
+            .send(command)
+            .expect("Failed to send command to drone");
+    }
+
+    pub fn inject(&self, packet: Packet) {
+        self.packet_send
+            .send(packet)
+            .expect("Failed to send packet to drone");
+    }
+
+    pub fn packet_send(&self) -> Sender<Packet> {
+        self.packet_send.clone()
+    }
+
+    pub fn recv_event(&self) -> RustDroneEvent {
+        self.event_recv
+            .recv_timeout(MAX_PACKET_WAIT_TIMEOUT)
+            .expect("Failed to receive event from drone")
+    }
+
+    pub fn event_recv(&self) -> Receiver<RustDroneEvent> {
+        self.event_recv.clone()
+    }
+
+    pub fn is_alive(&self) -> bool {
+        !self.thread.is_finished()
+    }
+
+    /// Drops the channels held by the handle, so that a crashing drone can finish draining
+    pub fn close(self) -> thread::JoinHandle<()> {
+        let NodeHandle {
+            thread,
+            packet_send,
+            command_send,
+            rust_command_send,
+            event_recv,
+        } = self;
+        drop((packet_send, command_send, rust_command_send, event_recv));
+
+        thread
+    }
+
+    /// Drops the channels held by the handle and waits for the drone thread to finish
+    pub fn join(self, timeout: Duration) -> Result<(), String> {
+        let thread = self.close();
+        let start_time = Instant::now();
+
+        while !thread.is_finished() {
+            if start_time.elapsed() >= timeout {
+                return Err("did not finish in time".to_string());
+            }
+            thread::sleep(DRONE_CRASH_POLL_INTERVAL);
+        }
+
+        join_finished(thread)
+    }
+}
+
+/// Joins a finished drone thread, which might have panicked, and reports its message
+fn join_finished(thread: thread::JoinHandle<()>) -> Result<(), String> {
+    thread.join().map_err(|e| {
+        let message = e
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| e.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic payload".to_string());
+
+        format!("panicked: {}", message)
+    })
+}
 
 pub fn generate_random_payload() -> (u8, [u8; 128]) {
     let payload_len = rand::rng().random_range(1..=128);
@@ -29,22 +120,56 @@ pub fn generate_random_payload() -> (u8, [u8; 128]) {
 }
 
 pub fn send_command_to_drone(hm: &Environment, drone_id: NodeId, command: DroneCommand) {
-    hm.get(&drone_id)
-        .unwrap()
-        .2
-        .send(command)
-        .expect("Failed to send command to drone");
+    hm.get(&drone_id).unwrap().command(command);
+}
+
+pub fn send_rust_command_to_drone(hm: &Environment, drone_id: NodeId, command: RustDroneCommand) {
+    hm.get(&drone_id).unwrap().rust_command(command);
 }
 
 pub fn send_packet_to_drone(hm: &Environment, drone_id: NodeId, packet: Packet) {
-    hm.get(&drone_id)
-        .unwrap()
-        .1
-        .send(packet)
-        .expect("Failed to send packet to drone");
+    hm.get(&drone_id).unwrap().inject(packet);
+}
+
+pub fn recv_event_from_drone(hm: &Environment, drone_id: NodeId) -> RustDroneEvent {
+    hm.get(&drone_id).unwrap().recv_event()
+}
+
+pub fn request_stats(hm: &Environment, drone_id: NodeId) -> DroneStats {
+    send_rust_command_to_drone(hm, drone_id, RustDroneCommand::ReportStats);
+
+    std::iter::from_fn(|| Some(recv_event_from_drone(hm, drone_id)))
+        .find_map(|event| match event {
+            RustDroneEvent::Stats(id, stats) if id == drone_id => Some(stats),
+            _ => None,
+        })
+        .expect("Failed to receive stats from drone")
+}
+
+pub fn request_snapshot(hm: &Environment, drone_id: NodeId) -> DroneSnapshot {
+    send_rust_command_to_drone(hm, drone_id, RustDroneCommand::QuerySnapshot);
+
+    std::iter::from_fn(|| Some(recv_event_from_drone(hm, drone_id)))
+        .find_map(|event| match event {
+            RustDroneEvent::Snapshot(id, snapshot) if id == drone_id => Some(snapshot),
+            _ => None,
+        })
+        .expect("Failed to receive snapshot from drone")
 }
 
 pub fn provision_drones_from_config(config: &Config) -> (Receiver<DroneEvent>, Environment) {
+    provision_customized_drones_from_config(config, |_, drone| drone)
+}
+
+/// Like `provision_drones_from_config`, but every drone goes through `customize` before running
+pub fn provision_customized_drones_from_config<F>(
+    config: &Config,
+    customize: F,
+) -> (Receiver<DroneEvent>, Environment)
+where
+    F: Fn(NodeId, RustDrone) -> RustDrone + Send + Sync + 'static,
+{
+    let customize = Arc::new(customize);
     let mut hm = HashMap::new();
     let mut d_loggers_targets = Vec::new();
 
@@ -56,25 +181,41 @@ pub fn provision_drones_from_config(config: &Config) -> (Receiver<DroneEvent>, E
         let drone_id = *drone_id;
         let (d_send, d_recv) = unbounded();
         let (d_command_send, d_command_recv) = unbounded();
+        let (d_rust_command_send, d_rust_command_recv) = unbounded();
+        let (d_event_send, d_event_recv) = unbounded();
         let clone_send = controller_send.clone();
+        let customize = customize.clone();
 
         let d_t = thread::Builder::new()
             .name(format!("drone-{}", drone_id))
             .spawn(move || {
-                let mut drone = RustDrone::new(
+                let drone = RustDrone::new(
                     drone_id,
                     clone_send,
                     d_command_recv,
                     d_recv,
                     HashMap::new(),
                     pdr,
-                );
+                )
+                .with_event_send(d_event_send)
+                .with_command_recv(d_rust_command_recv);
+
+                let mut drone = customize(drone_id, drone);
                 drone.run();
             })
             .expect("Failed to spawn drone thread");
 
         d_loggers_targets.push(format!("drone-{}", drone_id));
-        hm.insert(drone_id, (d_t, d_send, d_command_send));
+        hm.insert(
+            drone_id,
+            NodeHandle {
+                thread: d_t,
+                packet_send: d_send,
+                command_send: d_command_send,
+                rust_command_send: d_rust_command_send,
+                event_recv: d_event_recv,
+            },
+        );
     }
     let d_loggers_targets = d_loggers_targets
         .iter()
@@ -84,49 +225,65 @@ pub fn provision_drones_from_config(config: &Config) -> (Receiver<DroneEvent>, E
     init_logging_once_for(d_loggers_targets, log::LevelFilter::Trace, None);
 
     // join neighbours
-    for (drone_id, (_, _, d_command_send)) in hm.iter() {
+    for (drone_id, node) in hm.iter() {
         let (_, neighbours) = &config[drone_id];
 
         for neighbour in neighbours {
-            d_command_send
-                .send(DroneCommand::AddSender(
-                    *neighbour,
-                    hm.get(neighbour).unwrap().1.clone(),
-                ))
-                .expect("Failed to send AddSender command to drone");
+            node.command(DroneCommand::AddSender(
+                *neighbour,
+                hm.get(neighbour).unwrap().packet_send.clone(),
+            ));
         }
     }
 
     (controller_recv, hm)
 }
 
-pub fn terminate_env(mut hm: Environment, config: Config) {
-    for (id, (drone_t, _, d_command_send)) in hm.iter() {
-        assert!(!drone_t.is_finished());
+pub fn terminate_env(hm: Environment, config: Config) {
+    for (id, node) in hm.iter() {
+        assert!(node.is_alive());
         let (_, neighbours) = config.get(id).expect("Failed to get drone config");
 
         for neighbour in neighbours {
-            let neighbour_command_send = hm.get(neighbour).unwrap().2.clone();
-            let _ = neighbour_command_send.send(DroneCommand::RemoveSender(*id));
+            let _ = hm
+                .get(neighbour)
+                .unwrap()
+                .command_send
+                .send(DroneCommand::RemoveSender(*id));
         }
 
-        d_command_send
-            .send(DroneCommand::Crash)
-            .expect("Failed to send Crash command to drone");
+        node.command(DroneCommand::Crash);
     }
-    hm.clear();
+
+    // a crashing drone only stops once its packet channel is closed, so every handle
+    // has to release its senders before any drone can finish
+    let threads = hm
+        .into_iter()
+        .map(|(id, node)| (id, node.close()))
+        .collect::<Vec<_>>();
 
     let start_time = Instant::now();
 
     // check if all drones have finished, panic if not
-    while start_time.elapsed() < DRONE_CRASH_TIMEOUT {
-        if hm.iter().all(|(_, (drone_t, _, _))| drone_t.is_finished()) {
-            return;
+    while !threads.iter().all(|(_, thread)| thread.is_finished()) {
+        if start_time.elapsed() >= DRONE_CRASH_TIMEOUT {
+            let mut unfinished = threads
+                .iter()
+                .filter(|(_, thread)| !thread.is_finished())
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>();
+            unfinished.sort_unstable();
+
+            panic!("Drones {:?} did not finish in time", unfinished);
         }
         thread::sleep(DRONE_CRASH_POLL_INTERVAL);
     }
 
-    panic!("Not all drones have finished in time");
+    for (id, thread) in threads {
+        if let Err(e) = join_finished(thread) {
+            panic!("Drone '{}' {}", id, e);
+        }
+    }
 }
 
 pub fn generate_random_config() -> (u64, Config) {